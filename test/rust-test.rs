@@ -1,45 +1,651 @@
 use std::collections::HashMap;
 use std::fmt::{self, Display, Formatter};
+use std::future::Future;
+use std::hash::Hash;
+use std::num::NonZeroUsize;
 use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::{watch, RwLock, Semaphore, SemaphorePermit};
 use async_trait::async_trait;
+use futures::stream::{self, Stream, TryStreamExt};
+use lru::LruCache;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
 
 extern crate log;
 
 /// User represents a user in the system
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct User {
     pub id: u32,
+    pub uuid: Uuid,
     pub name: String,
     pub email: String,
     #[serde(skip)]
     password_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<SystemTime>,
+    #[serde(default)]
+    pub version: u64,
+    #[serde(default)]
+    pub roles: Vec<auth::Role>,
+    #[serde(default)]
+    pub status: Status,
+    #[serde(default)]
+    pub email_verified: bool,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, serde_json::Value>,
+    #[serde(with = "rfc3339", default = "default_timestamp")]
+    #[cfg_attr(feature = "schemars", schemars(with = "String"))]
+    pub created_at: SystemTime,
+    #[serde(with = "rfc3339", default = "default_timestamp")]
+    #[cfg_attr(feature = "schemars", schemars(with = "String"))]
+    pub updated_at: SystemTime,
+}
+
+/// PublicUser is the API-facing view of a User without any credential data
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct PublicUser {
+    pub uuid: Uuid,
+    pub name: String,
+    pub email: String,
+    #[serde(with = "rfc3339")]
+    #[cfg_attr(feature = "schemars", schemars(with = "String"))]
+    pub created_at: SystemTime,
+    #[serde(with = "rfc3339")]
+    #[cfg_attr(feature = "schemars", schemars(with = "String"))]
+    pub updated_at: SystemTime,
+}
+
+/// How `User::formatted_name` renders a user
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameStyle {
+    /// The name as stored
+    Plain,
+    /// `Name <email>`
+    WithEmail,
+    /// The uppercased first letter of each word in the name
+    Initials,
+}
+
+/// Field users are sorted on; ties fall back to id
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UserOrder {
+    #[default]
+    Id,
+    Name,
+    Email,
+}
+
+/// UserBuilder constructs validated users, hashing the password on build
+pub struct UserBuilder<'a> {
+    hasher: &'a dyn PasswordHasher,
+    name: String,
+    email: String,
+    password: Option<String>,
+}
+
+/// NewUser is the input for registering a user; the service assigns the id and hashes the password
+#[derive(Clone, Deserialize)]
+pub struct NewUser {
+    pub name: String,
+    pub email: String,
+    pub password: String,
 }
 
 /// UserService trait for user operations
 #[async_trait]
 pub trait UserService {
-    async fn get_user(&self, id: u32) -> Result<Option<User>, UserError>;
-    async fn create_user(&self, user: User) -> Result<User, UserError>;
-    async fn update_user(&self, user: User) -> Result<User, UserError>;
-    async fn delete_user(&self, id: u32) -> Result<(), UserError>;
-    async fn list_users(&self) -> Result<Vec<User>, UserError>;
+    async fn get_user(&self, id: u32) -> UserResult<Option<User>>;
+    async fn get_user_by_uuid(&self, uuid: Uuid) -> UserResult<Option<User>>;
+    async fn get_users(&self, ids: &[u32]) -> UserResult<Vec<Option<User>>>;
+    async fn create_user(&self, user: User) -> UserResult<User>;
+    async fn register_user(&self, new_user: NewUser, hasher: &dyn PasswordHasher) -> UserResult<User>;
+    async fn get_or_create_by_email(&self, new_user: NewUser, hasher: &dyn PasswordHasher) -> UserResult<(User, bool)>;
+    async fn update_user(&self, user: User) -> UserResult<User>;
+    async fn delete_user(&self, id: u32) -> UserResult<()>;
+    async fn list_users(&self) -> UserResult<Vec<User>>;
+    async fn list_users_paged(&self, offset: usize, limit: usize) -> UserResult<Page<User>>;
+    async fn list_users_after(&self, after_id: Option<u32>, limit: usize) -> UserResult<Vec<User>>;
+    async fn find_by_email(&self, email: &str) -> UserResult<Option<User>>;
+    async fn create_users(&self, users: Vec<User>) -> UserResult<BatchResult>;
+    async fn soft_delete_user(&self, id: u32) -> UserResult<()>;
+    async fn list_users_including_deleted(&self) -> UserResult<Vec<User>>;
+    async fn search_users(&self, query: UserQuery) -> UserResult<Vec<User>>;
+    async fn count_users(&self) -> UserResult<usize>;
+    async fn count_all(&self) -> UserResult<usize>;
+    async fn change_email(&self, id: u32, new_email: String) -> UserResult<User>;
+    async fn health_check(&self) -> UserResult<()>;
+    async fn list_users_by_status(&self, status: Status) -> UserResult<Vec<User>>;
+    async fn patch_user(&self, id: u32, patch: UserPatch) -> UserResult<User>;
+    async fn set_user_password(&self, id: u32, new_password: &str, hasher: &dyn PasswordHasher) -> UserResult<()>;
+    async fn mark_email_verified(&self, id: u32) -> UserResult<User>;
+    async fn set_active(&self, id: u32, active: bool) -> UserResult<User>;
+}
+
+/// UserStreamExt streams users from any UserService without collecting them all
+pub trait UserStreamExt: UserService {
+    /// Streams users in id order, fetching them in batches of `STREAM_BATCH_SIZE`
+    fn stream_users(&self) -> impl Stream<Item = Result<User, UserError>> + '_ {
+        self.stream_users_batched(STREAM_BATCH_SIZE)
+    }
+
+    /// Streams users in id order, fetching `batch_size` users per read
+    fn stream_users_batched(&self, batch_size: usize) -> impl Stream<Item = Result<User, UserError>> + '_ {
+        stream::try_unfold(Some(0usize), move |offset| async move {
+            let Some(offset) = offset else {
+                return Ok::<_, UserError>(None);
+            };
+
+            let page = self.list_users_paged(offset, batch_size.max(1)).await?;
+            let next = page.has_more.then(|| offset + page.items.len());
+            Ok(Some((stream::iter(page.items.into_iter().map(Ok::<User, UserError>)), next)))
+        })
+        .try_flatten()
+    }
+}
+
+impl<T: UserService + ?Sized> UserStreamExt for T {}
+
+/// UserServiceExt adds lookups that report missing users as `NotFound`, plus seeding and reporting helpers
+#[async_trait]
+pub trait UserServiceExt: UserService + Sync {
+    /// Checks whether an active user with `id` exists
+    async fn exists(&self, id: u32) -> Result<bool, UserError> {
+        Ok(self.get_user(id).await?.is_some())
+    }
+
+    /// Gets a user, failing with `NotFound` instead of returning `None`
+    async fn get_or_err(&self, id: u32) -> Result<User, UserError> {
+        self.get_user(id).await?.ok_or(UserError::NotFound)
+    }
+
+    /// Finds a user by email, failing with `NotFound` instead of returning `None`
+    async fn find_by_email_or_err(&self, email: &str) -> Result<User, UserError> {
+        self.find_by_email(email).await?.ok_or(UserError::NotFound)
+    }
+
+    /// Creates each user whose email isn't registered yet, so it is safe to run on every boot
+    async fn seed(&self, users: Vec<NewUser>, hasher: &dyn PasswordHasher) -> Result<(), UserError> {
+        for new_user in users {
+            let (user, created) = self.get_or_create_by_email(new_user, hasher).await?;
+            if created {
+                log::info!("Seeded user {} ({})", user.id, user.email);
+            }
+        }
+        Ok(())
+    }
+
+    /// Lists active users sorted by `order`
+    async fn list_users_ordered_by(&self, order: UserOrder) -> Result<Vec<User>, UserError> {
+        let mut users = self.list_users().await?;
+        order.sort(&mut users);
+        Ok(users)
+    }
+
+    /// Filters, sorts and pages users per `query`, reporting how many matched in total
+    async fn query_users(&self, query: UserQuery) -> Result<Page<User>, UserError> {
+        let matches = self
+            .search_users(UserQuery {
+                offset: 0,
+                limit: None,
+                ..query.clone()
+            })
+            .await?;
+
+        // Matches arrive already sorted, so only the window is left to apply
+        let total = matches.len();
+        let items: Vec<User> = matches
+            .into_iter()
+            .skip(query.offset)
+            .take(query.limit.unwrap_or(usize::MAX))
+            .collect();
+        let has_more = query.offset.saturating_add(items.len()) < total;
+        Ok(Page { items, total, has_more })
+    }
+
+    /// Counts active users per email domain, the part after the last `@`
+    async fn domain_histogram(&self) -> Result<HashMap<String, usize>, UserError> {
+        let mut histogram = HashMap::new();
+        for user in self.list_users().await? {
+            if let Some((_, domain)) = user.email.rsplit_once('@') {
+                *histogram.entry(domain.to_string()).or_insert(0) += 1;
+            }
+        }
+        Ok(histogram)
+    }
+}
+
+#[async_trait]
+impl<T: UserService + Sync + ?Sized> UserServiceExt for T {}
+
+/// Shares one service between owners; every call goes to the same underlying state
+#[async_trait]
+impl<T: UserService + Send + Sync + ?Sized> UserService for Arc<T> {
+    async fn get_user(&self, id: u32) -> UserResult<Option<User>> {
+        (**self).get_user(id).await
+    }
+
+    async fn get_user_by_uuid(&self, uuid: Uuid) -> UserResult<Option<User>> {
+        (**self).get_user_by_uuid(uuid).await
+    }
+
+    async fn get_users(&self, ids: &[u32]) -> UserResult<Vec<Option<User>>> {
+        (**self).get_users(ids).await
+    }
+
+    async fn create_user(&self, user: User) -> UserResult<User> {
+        (**self).create_user(user).await
+    }
+
+    async fn register_user(&self, new_user: NewUser, hasher: &dyn PasswordHasher) -> UserResult<User> {
+        (**self).register_user(new_user, hasher).await
+    }
+
+    async fn get_or_create_by_email(&self, new_user: NewUser, hasher: &dyn PasswordHasher) -> UserResult<(User, bool)> {
+        (**self).get_or_create_by_email(new_user, hasher).await
+    }
+
+    async fn update_user(&self, user: User) -> UserResult<User> {
+        (**self).update_user(user).await
+    }
+
+    async fn delete_user(&self, id: u32) -> UserResult<()> {
+        (**self).delete_user(id).await
+    }
+
+    async fn list_users(&self) -> UserResult<Vec<User>> {
+        (**self).list_users().await
+    }
+
+    async fn list_users_paged(&self, offset: usize, limit: usize) -> UserResult<Page<User>> {
+        (**self).list_users_paged(offset, limit).await
+    }
+
+    async fn list_users_after(&self, after_id: Option<u32>, limit: usize) -> UserResult<Vec<User>> {
+        (**self).list_users_after(after_id, limit).await
+    }
+
+    async fn find_by_email(&self, email: &str) -> UserResult<Option<User>> {
+        (**self).find_by_email(email).await
+    }
+
+    async fn create_users(&self, users: Vec<User>) -> UserResult<BatchResult> {
+        (**self).create_users(users).await
+    }
+
+    async fn soft_delete_user(&self, id: u32) -> UserResult<()> {
+        (**self).soft_delete_user(id).await
+    }
+
+    async fn list_users_including_deleted(&self) -> UserResult<Vec<User>> {
+        (**self).list_users_including_deleted().await
+    }
+
+    async fn search_users(&self, query: UserQuery) -> UserResult<Vec<User>> {
+        (**self).search_users(query).await
+    }
+
+    async fn count_users(&self) -> UserResult<usize> {
+        (**self).count_users().await
+    }
+
+    async fn count_all(&self) -> UserResult<usize> {
+        (**self).count_all().await
+    }
+
+    async fn change_email(&self, id: u32, new_email: String) -> UserResult<User> {
+        (**self).change_email(id, new_email).await
+    }
+
+    async fn health_check(&self) -> UserResult<()> {
+        (**self).health_check().await
+    }
+
+    async fn list_users_by_status(&self, status: Status) -> UserResult<Vec<User>> {
+        (**self).list_users_by_status(status).await
+    }
+
+    async fn patch_user(&self, id: u32, patch: UserPatch) -> UserResult<User> {
+        (**self).patch_user(id, patch).await
+    }
+
+    async fn set_user_password(&self, id: u32, new_password: &str, hasher: &dyn PasswordHasher) -> UserResult<()> {
+        (**self).set_user_password(id, new_password, hasher).await
+    }
+
+    async fn mark_email_verified(&self, id: u32) -> UserResult<User> {
+        (**self).mark_email_verified(id).await
+    }
+
+    async fn set_active(&self, id: u32, active: bool) -> UserResult<User> {
+        (**self).set_active(id, active).await
+    }
+}
+
+/// CloneUserService lets boxed trait objects be cloned when the concrete service is `Clone`
+pub trait CloneUserService: UserService + Send + Sync {
+    /// Clones the service behind a fresh box
+    fn clone_box(&self) -> Box<dyn CloneUserService>;
+}
+
+impl<T: UserService + Clone + Send + Sync + 'static> CloneUserService for T {
+    fn clone_box(&self) -> Box<dyn CloneUserService> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn CloneUserService> {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+/// UserCsvExt exports and imports users as CSV; password hashes are never exported
+#[async_trait]
+pub trait UserCsvExt: UserService + Sync {
+    /// Writes every active user as an id,name,email row
+    async fn export_users_csv(&self) -> Result<String, UserError> {
+        let mut users = self.list_users().await?;
+        users.sort_by_key(|user| user.id);
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for user in users {
+            writer
+                .serialize(CsvUserRow { id: user.id, name: user.name, email: user.email })
+                .map_err(|e| UserError::InvalidInput(format!("CSV export failed: {}", e)))?;
+        }
+        let data = writer
+            .into_inner()
+            .map_err(|e| UserError::InvalidInput(format!("CSV export failed: {}", e)))?;
+        String::from_utf8(data).map_err(|e| UserError::InvalidInput(format!("CSV export failed: {}", e)))
+    }
+
+    /// Creates a user per CSV row, assigning fresh ids; failures are keyed by row index
+    async fn import_users_csv(&self, data: &str) -> Result<BatchResult, UserError> {
+        let mut reader = csv::Reader::from_reader(data.as_bytes());
+        let mut rows = Vec::new();
+        let mut parse_failures = Vec::new();
+        for (index, record) in reader.deserialize::<CsvUserRow>().enumerate() {
+            match record {
+                Ok(row) => rows.push((index, User::new(row.name, row.email))),
+                Err(e) => parse_failures.push((index, UserError::InvalidInput(format!("invalid CSV row: {}", e)))),
+            }
+        }
+
+        let (indices, users): (Vec<usize>, Vec<User>) = rows.into_iter().unzip();
+        let mut result = self.create_users(users).await?;
+        for (index, _) in result.failed.iter_mut() {
+            *index = indices[*index];
+        }
+        result.failed.extend(parse_failures);
+        result.failed.sort_by_key(|(index, _)| *index);
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl<T: UserService + Sync + ?Sized> UserCsvExt for T {}
+
+/// UserJsonlExt backs up users as JSON Lines, one `PublicUser` per line
+#[async_trait]
+pub trait UserJsonlExt: UserService + Sync {
+    /// Writes every active user in id order, fetching `STREAM_BATCH_SIZE` at a time, and returns the count
+    async fn export_jsonl<W: AsyncWrite + Unpin + Send>(&self, mut writer: W) -> Result<u64, UserError> {
+        let export_error = |e: std::io::Error| UserError::InvalidInput(format!("JSONL export failed: {}", e));
+        let mut written = 0;
+        let mut after_id = None;
+        loop {
+            let batch = self.list_users_after(after_id, STREAM_BATCH_SIZE).await?;
+            let Some(last) = batch.last() else {
+                break;
+            };
+            after_id = Some(last.id);
+
+            for user in batch {
+                let mut line = serde_json::to_vec(&user.to_public())
+                    .map_err(|e| UserError::InvalidInput(format!("JSONL export failed: {}", e)))?;
+                line.push(b'\n');
+                writer.write_all(&line).await.map_err(export_error)?;
+                written += 1;
+            }
+        }
+        writer.flush().await.map_err(export_error)?;
+        Ok(written)
+    }
+
+    /// Restores `PublicUser` lines, resolving email conflicts with `on_conflict`.
+    ///
+    /// New users get fresh ids. `ConflictPolicy::Error` stops at the first conflict, keeping earlier lines.
+    async fn import_jsonl<R: AsyncBufRead + Unpin + Send>(
+        &self,
+        reader: R,
+        on_conflict: ConflictPolicy,
+    ) -> Result<ImportReport, UserError> {
+        let mut report = ImportReport::default();
+        let mut lines = reader.lines();
+        let mut line_number = 0;
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .map_err(|e| UserError::InvalidInput(format!("JSONL import failed: {}", e)))?
+        {
+            line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: PublicUser = serde_json::from_str(&line)
+                .map_err(|e| UserError::InvalidInput(format!("invalid JSONL line {}: {}", line_number, e)))?;
+
+            match (self.find_by_email(&record.email).await?, on_conflict) {
+                (None, _) => {
+                    self.create_user(User::new(record.name, record.email)).await?;
+                    report.imported += 1;
+                }
+                (Some(_), ConflictPolicy::Skip) => report.skipped += 1,
+                (Some(mut existing), ConflictPolicy::Overwrite) => {
+                    existing.name = record.name;
+                    self.update_user(existing).await?;
+                    report.overwritten += 1;
+                }
+                (Some(_), ConflictPolicy::Error) => {
+                    return Err(UserError::InvalidInput(format!(
+                        "email already registered on line {}",
+                        line_number
+                    )));
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+#[async_trait]
+impl<T: UserService + Sync + ?Sized> UserJsonlExt for T {}
+
+/// CSV row layout shared by export and import
+#[derive(Debug, Serialize, Deserialize)]
+struct CsvUserRow {
+    id: u32,
+    name: String,
+    email: String,
+}
+
+/// ServiceLayer wraps a UserService in another UserService, so behavior can be stacked
+pub trait ServiceLayer<S: UserService> {
+    type Service: UserService;
+
+    fn wrap(&self, inner: S) -> Self::Service;
+}
+
+/// Interceptor observes every call made through a `Layered` service
+pub trait Interceptor: Send + Sync {
+    fn before(&self, _operation: &'static str) {}
+    fn after(&self, _operation: &'static str, _elapsed: Duration, _error: Option<&UserError>) {}
+}
+
+/// Layered delegates to `inner`, running `interceptor` around each call
+pub struct Layered<S, I> {
+    inner: S,
+    interceptor: I,
+}
+
+/// LoggingLayer logs the start and outcome of every call on the `user_service` target
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LoggingLayer;
+
+/// TimingLayer logs how long every call took on the `user_service` target
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimingLayer;
+
+/// Repository is a minimal keyed store that caching can be layered over
+#[async_trait]
+pub trait Repository<K: Send + Sync, V: Send>: Send + Sync {
+    async fn get(&self, key: &K) -> Result<Option<V>, UserError>;
+    async fn insert(&self, key: K, value: V) -> Result<(), UserError>;
+    async fn remove(&self, key: &K) -> Result<Option<V>, UserError>;
+    async fn list(&self) -> Result<Vec<V>, UserError>;
+}
+
+/// ReadRouter picks the backend handle for writes and for reads, which may go to a replica
+pub trait ReadRouter {
+    type Handle: ?Sized;
+
+    /// Handle for writes and reads that must see them
+    fn writer(&self) -> &Self::Handle;
+
+    /// Handle for reads that tolerate replication lag; defaults to the writer
+    fn reader(&self) -> &Self::Handle {
+        self.writer()
+    }
+}
+
+/// Filters for UserService::search_users; unset fields match every user.
+///
+/// `name_contains` ignores case and Unicode normalization form but not accents.
+/// Matches are sorted by `sort_by`, then `offset` are skipped and at most `limit` returned.
+#[derive(Debug, Clone, Default)]
+pub struct UserQuery {
+    pub name_contains: Option<String>,
+    pub email_domain: Option<String>,
+    pub sort_by: UserOrder,
+    pub descending: bool,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+/// Partial update for UserService::patch_user; unset fields are left unchanged
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct UserPatch {
+    pub name: Option<String>,
+    pub email: Option<String>,
+}
+
+/// Page of results sorted by id
+#[derive(Debug, Clone, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+    pub has_more: bool,
+}
+
+/// JSON error shape returned to API clients
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// Outcome of a bulk operation, with failures keyed by input index
+#[derive(Debug, Default)]
+pub struct BatchResult {
+    pub created: Vec<User>,
+    pub failed: Vec<(usize, UserError)>,
+}
+
+/// ConflictPolicy decides what an import does with a record whose email is already registered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+    Error,
+}
+
+/// Counts of records handled by a JSONL import
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub skipped: usize,
+    pub overwritten: usize,
 }
 
 /// Application configuration
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub port: u16,
     pub database_url: String,
+    #[serde(skip_serializing)]
+    #[cfg_attr(feature = "schemars", schemars(skip))]
     pub jwt_secret: String,
     #[serde(default = "default_max_connections")]
     pub max_connections: u32,
+    /// Argon2 iteration count; `None` keeps the library default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password_cost: Option<u32>,
+    #[serde(default = "default_access_token_ttl_secs")]
+    pub access_token_ttl_secs: u64,
+    #[serde(default = "default_refresh_token_ttl_secs")]
+    pub refresh_token_ttl_secs: u64,
+    /// Read-only replica for queries; `None` sends reads to `database_url`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_replica_url: Option<String>,
+}
+
+/// UserActionEvent is the structured record emitted by `log_user_action!`
+#[derive(Debug, Clone, Serialize)]
+pub struct UserActionEvent {
+    pub action: &'static str,
+    pub user_id: u32,
+    #[serde(with = "rfc3339")]
+    pub at: SystemTime,
+}
+
+/// ConfigBuilder assembles a Config without reading the environment
+#[derive(Debug, Default)]
+pub struct ConfigBuilder {
+    port: Option<u16>,
+    database_url: Option<String>,
+    jwt_secret: Option<String>,
+    max_connections: Option<u32>,
+    password_cost: Option<u32>,
+    access_token_ttl_secs: Option<u64>,
+    refresh_token_ttl_secs: Option<u64>,
+    read_replica_url: Option<String>,
+}
+
+/// ConfigWatcher keeps a config file watch alive and stops it when dropped
+pub struct ConfigWatcher {
+    task: tokio::task::JoinHandle<()>,
 }
 
 /// Status enumeration for operations
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
 pub enum Status {
+    #[default]
     Pending,
     InProgress,
     Completed,
@@ -57,6 +663,36 @@ pub enum UserError {
     Database(#[from] DatabaseError),
     #[error("Authentication error")]
     Authentication,
+    #[error("Password hashing failed: {0}")]
+    Hashing(String),
+    #[error("Version conflict")]
+    Conflict,
+    #[error("Too many attempts, try again later")]
+    RateLimited,
+    #[error("Service is shutting down")]
+    Unavailable,
+    #[error("Configuration error: {0}")]
+    Config(#[from] ConfigError),
+    #[error("Account is temporarily locked")]
+    Locked,
+}
+
+/// Result type shared by UserService and the rest of the user API
+pub type UserResult<T> = Result<T, UserError>;
+
+/// Error types for loading and validating configuration
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("{0} environment variable is required")]
+    MissingVar(&'static str),
+    #[error("Invalid {0}: {1}")]
+    InvalidVar(&'static str, String),
+    #[error("Invalid {format} config: {message}")]
+    Parse { format: &'static str, message: String },
+    #[error("{0}")]
+    Invalid(String),
+    #[error("Failed to read config: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 /// Database error types
@@ -68,17 +704,172 @@ pub enum DatabaseError {
     QueryFailed(String),
 }
 
+/// PasswordHasher abstracts the algorithm used to hash and verify passwords
+pub trait PasswordHasher: Send + Sync {
+    fn hash(&self, password: &str) -> Result<String, UserError>;
+
+    /// Verifies by re-hashing and comparing in constant time; salted backends override this
+    fn verify(&self, password: &str, hash: &str) -> Result<bool, UserError> {
+        Ok(utils::constant_time_eq(&self.hash(password)?, hash))
+    }
+
+    /// Reports whether `hash` was produced with outdated parameters and should be replaced
+    fn needs_rehash(&self, _hash: &str) -> bool {
+        false
+    }
+
+    /// Spends as long as a real verification without a stored hash, so unknown emails aren't faster
+    fn verify_dummy(&self, password: &str) {
+        let _ = self.verify(password, DUMMY_PASSWORD_HASH);
+    }
+}
+
+/// PlaintextHasher is an insecure hasher intended only for tests
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PlaintextHasher;
+
+/// Clock is the source of the current time for caches, lockouts and token expiry
+pub trait Clock: Send + Sync {
+    /// Monotonic time, used for TTLs and lockouts
+    fn now(&self) -> Instant;
+
+    /// Wall-clock time, used for token expiry
+    fn system_now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// SystemClock reads the real time
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+/// TestClock starts at the real time and only moves when `advance` is called
+#[derive(Debug)]
+pub struct TestClock {
+    start: Instant,
+    start_system: SystemTime,
+    offset: Mutex<Duration>,
+}
+
+/// Argon2Hasher hashes passwords with Argon2id and a random salt
+#[cfg(feature = "argon2")]
+#[derive(Default)]
+pub struct Argon2Hasher {
+    argon2: argon2::Argon2<'static>,
+    dummy_hash: std::sync::OnceLock<String>,
+}
+
 /// Constants
 pub const DEFAULT_PORT: u16 = 8080;
+/// Maximum username length, counted in characters rather than bytes
 pub const MAX_USERNAME_LENGTH: usize = 100;
 pub const VERSION: &str = "1.0.0";
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+pub const DEFAULT_CACHE_CAPACITY: usize = 10_000;
+pub const STREAM_BATCH_SIZE: usize = 100;
+pub const CONFIG_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const CONFIG_VARS: &[&str] = &[
+    "PORT",
+    "DATABASE_URL",
+    "JWT_SECRET",
+    "MAX_CONNECTIONS",
+    "PASSWORD_COST",
+    "ACCESS_TOKEN_TTL_SECS",
+    "REFRESH_TOKEN_TTL_SECS",
+    "READ_REPLICA_URL",
+];
+pub const DEFAULT_SHARD_COUNT: usize = 16;
+pub const DUMMY_PASSWORD_HASH: &str = "dummy-password-hash";
 
-static mut GLOBAL_COUNTER: u32 = 0;
+static GLOBAL_COUNTER: AtomicU32 = AtomicU32::new(0);
 
 /// UserServiceImpl provides concrete implementation of UserService
+///
+/// Locks are always taken in the order `next_id`, `emails`, `uuids`, user store.
+/// The `emails` and `uuids` indexes are only changed after the last await of a write, so a
+/// cancelled future can never leave them out of step with the user store.
 pub struct UserServiceImpl {
-    users: RwLock<HashMap<u32, User>>,
+    users: UserStore,
+    emails: RwLock<HashMap<String, u32>>,
+    uuids: RwLock<HashMap<Uuid, u32>>,
     next_id: RwLock<u32>,
+    listeners: Vec<Arc<dyn EventListener>>,
+    shutdown: CancellationToken,
+    limit: Option<ConnectionSemaphore>,
+}
+
+/// ConnectionSemaphore caps how many operations may run against a backend at once
+pub struct ConnectionSemaphore {
+    permits: Semaphore,
+}
+
+/// EventListener is notified after user lifecycle changes succeed
+pub trait EventListener: Send + Sync {
+    fn on_created(&self, _user: &User) {}
+    fn on_updated(&self, _user: &User) {}
+    fn on_deleted(&self, _id: u32) {}
+}
+
+/// Kind of mutation recorded in the audit log
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// AuditEntry records one mutation; `actor` is None when no acting user is known
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditEntry {
+    pub action: AuditAction,
+    pub user_id: u32,
+    #[serde(with = "rfc3339")]
+    pub at: SystemTime,
+    pub actor: Option<u32>,
+}
+
+/// AuditSink receives an append-only stream of audit entries
+pub trait AuditSink: Send + Sync {
+    fn record(&self, entry: AuditEntry);
+}
+
+/// InMemoryAuditSink keeps audit entries in memory for inspection
+#[derive(Debug, Default)]
+pub struct InMemoryAuditSink {
+    entries: Mutex<Vec<AuditEntry>>,
+}
+
+/// AuditListener forwards lifecycle events to an AuditSink
+pub struct AuditListener {
+    sink: Arc<dyn AuditSink>,
+    actor: Option<u32>,
+}
+
+/// Backing store for UserServiceImpl's users; swap in DashMap with the `dashmap` feature
+#[cfg(not(feature = "dashmap"))]
+type UserStore = ShardedMap<User>;
+#[cfg(feature = "dashmap")]
+type UserStore = DashMapStore<User>;
+
+/// ShardedMap spreads values over independently locked shards chosen by `id % shards`.
+///
+/// Values are kept behind `Arc` so readers can share them; writes copy a value only while it is shared.
+#[cfg(not(feature = "dashmap"))]
+struct ShardedMap<V> {
+    shards: Vec<RwLock<HashMap<u32, Arc<V>>>>,
+}
+
+/// ShardsWriteGuard holds the write lock of every shard in a ShardedMap
+#[cfg(not(feature = "dashmap"))]
+struct ShardsWriteGuard<'a, V> {
+    guards: Vec<tokio::sync::RwLockWriteGuard<'a, HashMap<u32, Arc<V>>>>,
+}
+
+/// DashMapStore keeps values in a DashMap, whose synchronous shard locks are never held across an await
+#[cfg(feature = "dashmap")]
+struct DashMapStore<V> {
+    map: dashmap::DashMap<u32, Arc<V>>,
 }
 
 impl User {
@@ -86,47 +877,140 @@ impl User {
     pub fn new(name: String, email: String) -> Self {
         Self {
             id: 0,
+            uuid: Uuid::new_v4(),
             name,
             email,
             password_hash: String::new(),
+            deleted_at: None,
+            version: 0,
+            roles: vec![auth::Role::User],
+            status: Status::Pending,
+            email_verified: false,
+            active: true,
+            metadata: HashMap::new(),
+            created_at: UNIX_EPOCH,
+            updated_at: UNIX_EPOCH,
         }
     }
 
     /// Validates user data
     pub fn validate(&self) -> Result<(), UserError> {
+        self.validate_all().map_err(|mut errors| errors.remove(0))
+    }
+
+    /// Validates user data, collecting every failure instead of stopping at the first
+    pub fn validate_all(&self) -> Result<(), Vec<UserError>> {
+        let mut errors = Vec::new();
+
         if self.name.is_empty() {
-            return Err(UserError::InvalidInput("Name is required".to_string()));
+            errors.push(UserError::InvalidInput("Name is required".to_string()));
         }
         
-        if self.email.is_empty() {
-            return Err(UserError::InvalidInput("Email is required".to_string()));
+        let email = self.email_normalized();
+        if email.is_empty() {
+            errors.push(UserError::InvalidInput("Email is required".to_string()));
+        } else if !utils::is_valid_email(&email) {
+            errors.push(UserError::InvalidInput("Invalid email format".to_string()));
         }
         
-        if !self.email.contains('@') {
-            return Err(UserError::InvalidInput("Invalid email format".to_string()));
+        if self.name.chars().count() > MAX_USERNAME_LENGTH {
+            errors.push(UserError::InvalidInput("Username too long".to_string()));
         }
         
-        if self.name.len() > MAX_USERNAME_LENGTH {
-            return Err(UserError::InvalidInput("Username too long".to_string()));
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
         }
-        
+    }
+
+    /// Sets the user's password using the given hasher
+    pub fn set_password(&mut self, password: &str, hasher: &dyn PasswordHasher) -> Result<(), UserError> {
+        auth::validate_password_strength(password)?;
+        self.password_hash = hasher.hash(password)?;
         Ok(())
     }
 
-    /// Sets the user's password
-    pub fn set_password(&mut self, password: &str) {
-        self.password_hash = hash_password(password);
+    /// Checks if password is correct using the given hasher, taking as long when no password is set
+    pub fn verify_password(&self, password: &str, hasher: &dyn PasswordHasher) -> Result<bool, UserError> {
+        if self.password_hash.is_empty() {
+            hasher.verify_dummy(password);
+            return Ok(false);
+        }
+        hasher.verify(password, &self.password_hash)
+    }
+
+    /// Sets an application-defined attribute, returning the value it replaced
+    pub fn set_meta(&mut self, key: impl Into<String>, value: impl Into<serde_json::Value>) -> Option<serde_json::Value> {
+        self.metadata.insert(key.into(), value.into())
+    }
+
+    /// Gets an application-defined attribute
+    pub fn get_meta(&self, key: &str) -> Option<&serde_json::Value> {
+        self.metadata.get(key)
+    }
+
+    /// Returns the email trimmed and lowercased, as used for lookups
+    pub fn email_normalized(&self) -> String {
+        utils::normalize_email(&self.email)
+    }
+
+    /// Checks if the user has been soft-deleted
+    pub fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
     }
 
-    /// Checks if password is correct
-    pub fn verify_password(&self, password: &str) -> bool {
-        self.password_hash == hash_password(password)
+    /// Checks if the user holds the admin role
+    pub fn is_admin(&self) -> bool {
+        self.roles.contains(&auth::Role::Admin)
     }
 
     /// Gets the user's display name
     pub fn display_name(&self) -> &str {
         &self.name
     }
+
+    /// Formats the user's name in the given style
+    pub fn formatted_name(&self, style: NameStyle) -> String {
+        match style {
+            NameStyle::Plain => self.name.clone(),
+            NameStyle::WithEmail => format!("{} <{}>", self.name, self.email),
+            NameStyle::Initials => self
+                .name
+                .split_whitespace()
+                .filter_map(|word| word.chars().next())
+                .flat_map(char::to_uppercase)
+                .collect(),
+        }
+    }
+
+    /// Returns the public view of this user
+    pub fn to_public(&self) -> PublicUser {
+        PublicUser {
+            uuid: self.uuid,
+            name: self.name.clone(),
+            email: self.email.clone(),
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        }
+    }
+
+    /// Starts building a user whose password will be hashed with `hasher`
+    pub fn builder(hasher: &dyn PasswordHasher) -> UserBuilder<'_> {
+        UserBuilder::new(hasher)
+    }
+}
+
+impl UserPatch {
+    /// Copies the set fields onto `user`, normalizing the email
+    pub fn apply_to(self, user: &mut User) {
+        if let Some(name) = self.name {
+            user.name = name;
+        }
+        if let Some(email) = self.email {
+            user.email = utils::normalize_email(&email);
+        }
+    }
 }
 
 impl Display for User {
@@ -135,302 +1019,6547 @@ impl Display for User {
     }
 }
 
-impl UserServiceImpl {
-    /// Creates a new UserService instance
-    pub fn new() -> Self {
-        Self {
-            users: RwLock::new(HashMap::new()),
-            next_id: RwLock::new(1),
+impl From<User> for PublicUser {
+    fn from(user: User) -> Self {
+        PublicUser {
+            uuid: user.uuid,
+            name: user.name,
+            email: user.email,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
         }
     }
+}
 
-    /// Gets the next available user ID
-    async fn next_user_id(&self) -> u32 {
-        let mut next_id = self.next_id.write().await;
-        let id = *next_id;
-        *next_id += 1;
-        id
+impl<'a> UserBuilder<'a> {
+    /// Creates an empty builder using the given password hasher
+    pub fn new(hasher: &'a dyn PasswordHasher) -> Self {
+        Self {
+            hasher,
+            name: String::new(),
+            email: String::new(),
+            password: None,
+        }
     }
-}
 
-#[async_trait]
-impl UserService for UserServiceImpl {
-    async fn get_user(&self, id: u32) -> Result<Option<User>, UserError> {
-        let users = self.users.read().await;
-        Ok(users.get(&id).cloned())
+    /// Sets the user's name
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
     }
 
-    async fn create_user(&self, mut user: User) -> Result<User, UserError> {
-        user.validate()?;
-        
-        let id = self.next_user_id().await;
-        user.id = id;
-        
-        let mut users = self.users.write().await;
-        users.insert(id, user.clone());
-        
-        Ok(user)
+    /// Sets the user's email
+    pub fn email(mut self, email: impl Into<String>) -> Self {
+        self.email = email.into();
+        self
     }
 
-    async fn update_user(&self, user: User) -> Result<User, UserError> {
+    /// Sets the plaintext password to hash on build
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Validates the user and hashes the password if one was provided
+    pub fn build(self) -> Result<User, UserError> {
+        let mut user = User::new(self.name, utils::normalize_email(&self.email));
         user.validate()?;
-        
-        let mut users = self.users.write().await;
-        if users.contains_key(&user.id) {
-            users.insert(user.id, user.clone());
-            Ok(user)
-        } else {
-            Err(UserError::NotFound)
+
+        if let Some(password) = self.password {
+            user.set_password(&password, self.hasher)?;
         }
+
+        Ok(user)
     }
+}
 
-    async fn delete_user(&self, id: u32) -> Result<(), UserError> {
-        let mut users = self.users.write().await;
-        users.remove(&id).ok_or(UserError::NotFound)?;
-        Ok(())
+impl NewUser {
+    /// Creates registration input from plaintext fields
+    pub fn new(name: impl Into<String>, email: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            email: email.into(),
+            password: password.into(),
+        }
     }
 
-    async fn list_users(&self) -> Result<Vec<User>, UserError> {
-        let users = self.users.read().await;
-        Ok(users.values().cloned().collect())
+    /// Validates the input and hashes the password into an unsaved user
+    pub fn into_user(self, hasher: &dyn PasswordHasher) -> Result<User, UserError> {
+        User::builder(hasher)
+            .name(self.name)
+            .email(self.email)
+            .password(self.password)
+            .build()
     }
 }
 
-impl Config {
-    /// Loads configuration from environment variables
-    pub fn from_env() -> Result<Self, Box<dyn Error>> {
-        let port = std::env::var("PORT")
-            .unwrap_or_else(|_| DEFAULT_PORT.to_string())
-            .parse()?;
-            
-        let database_url = std::env::var("DATABASE_URL")
-            .map_err(|_| "DATABASE_URL environment variable is required")?;
-            
-        let jwt_secret = std::env::var("JWT_SECRET")
-            .map_err(|_| "JWT_SECRET environment variable is required")?;
-            
-        let max_connections = std::env::var("MAX_CONNECTIONS")
-            .unwrap_or_else(|_| default_max_connections().to_string())
-            .parse()?;
+impl fmt::Debug for NewUser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NewUser")
+            .field("name", &self.name)
+            .field("email", &self.email)
+            .finish_non_exhaustive()
+    }
+}
 
-        Ok(Config {
-            port,
-            database_url,
-            jwt_secret,
-            max_connections,
-        })
+impl UserQuery {
+    /// Checks if a user satisfies the name and email domain filters
+    pub fn matches(&self, user: &User) -> bool {
+        let name_matches = self
+            .name_contains
+            .as_ref()
+            .is_none_or(|needle| utils::fold_name(&user.name).contains(&utils::fold_name(needle)));
+        let domain_matches = self.email_domain.as_ref().is_none_or(|domain| {
+            user.email.rsplit_once('@').map(|(_, d)| d) == Some(utils::normalize_email(domain).as_str())
+        });
+        name_matches && domain_matches
     }
 
-    /// Validates the configuration
-    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
-        if self.port == 0 {
-            return Err("Port must be greater than 0".into());
+    /// Sorts `users` by `sort_by` and keeps the window selected by `offset` and `limit`
+    fn order_and_page(&self, mut users: Vec<User>) -> Vec<User> {
+        self.sort_by.sort(&mut users);
+        if self.descending {
+            users.reverse();
         }
-        
-        if self.database_url.is_empty() {
-            return Err("Database URL cannot be empty".into());
+        users
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+impl UserOrder {
+    /// Sorts `users` ascending by this field, breaking ties by id
+    fn sort(self, users: &mut [User]) {
+        match self {
+            UserOrder::Id => users.sort_by_key(|user| user.id),
+            UserOrder::Name => users.sort_by(|a, b| a.name.cmp(&b.name).then(a.id.cmp(&b.id))),
+            UserOrder::Email => users.sort_by(|a, b| a.email.cmp(&b.email).then(a.id.cmp(&b.id))),
         }
-        
-        if self.jwt_secret.len() < 32 {
-            return Err("JWT secret must be at least 32 characters".into());
+    }
+
+    /// Returns the matching SQL ORDER BY clause body
+    #[cfg(feature = "postgres")]
+    fn sql(self, descending: bool) -> &'static str {
+        match (self, descending) {
+            (UserOrder::Id, false) => "id",
+            (UserOrder::Id, true) => "id DESC",
+            (UserOrder::Name, false) => "name, id",
+            (UserOrder::Name, true) => "name DESC, id DESC",
+            (UserOrder::Email, false) => "email, id",
+            (UserOrder::Email, true) => "email DESC, id DESC",
         }
-        
-        Ok(())
     }
 }
 
-impl Status {
-    /// Checks if the status represents a completed state
-    pub fn is_completed(&self) -> bool {
-        matches!(self, Status::Completed | Status::Failed)
+impl UserError {
+    /// Returns a stable machine-readable code for API responses
+    pub fn code(&self) -> &'static str {
+        match self {
+            UserError::NotFound => "not_found",
+            UserError::InvalidInput(_) => "invalid_input",
+            UserError::Database(_) => "database_error",
+            UserError::Authentication => "authentication",
+            UserError::Hashing(_) => "hashing_error",
+            UserError::Conflict => "conflict",
+            UserError::RateLimited => "rate_limited",
+            UserError::Unavailable => "unavailable",
+            UserError::Config(_) => "config_error",
+            UserError::Locked => "locked",
+        }
     }
 
-    /// Checks if the status represents an active state
-    pub fn is_active(&self) -> bool {
-        matches!(self, Status::Pending | Status::InProgress)
+    /// Checks if the error is likely to succeed when retried
+    pub fn is_transient(&self) -> bool {
+        matches!(self, UserError::Database(DatabaseError::ConnectionFailed))
     }
 
-    /// Returns the status as a string
-    pub fn as_str(&self) -> &'static str {
+    /// Returns the HTTP status code this error maps to
+    pub fn http_status(&self) -> u16 {
         match self {
-            Status::Pending => "pending",
-            Status::InProgress => "in_progress",
-            Status::Completed => "completed",
-            Status::Failed => "failed",
+            UserError::NotFound => 404,
+            UserError::InvalidInput(_) => 400,
+            UserError::Database(_) => 500,
+            UserError::Authentication => 401,
+            UserError::Hashing(_) => 500,
+            UserError::Conflict => 409,
+            UserError::RateLimited => 429,
+            UserError::Unavailable => 503,
+            UserError::Config(_) => 500,
+            UserError::Locked => 423,
         }
     }
-}
 
-impl Display for Status {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.as_str())
+    /// Builds the client-facing error body, hiding internal details behind a generic message
+    pub fn to_error_body(&self) -> ErrorBody {
+        let message = match self {
+            UserError::Database(_) | UserError::Hashing(_) | UserError::Config(_) => "Internal server error".to_string(),
+            other => other.to_string(),
+        };
+        ErrorBody {
+            code: self.code(),
+            message,
+        }
     }
 }
 
-/// UserManager provides high-level user management operations
-pub struct UserManager<T: UserService> {
-    service: T,
-    cache: RwLock<HashMap<u32, User>>,
+/// Serializes as the `ErrorBody` shape
+impl Serialize for UserError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.to_error_body().serialize(serializer)
+    }
 }
 
-impl<T: UserService> UserManager<T> {
-    /// Creates a new UserManager
-    pub fn new(service: T) -> Self {
+impl ConnectionSemaphore {
+    /// Allows `max_connections` concurrent operations, at least one
+    pub fn new(max_connections: u32) -> Self {
         Self {
-            service,
-            cache: RwLock::new(HashMap::new()),
+            permits: Semaphore::new(max_connections.max(1) as usize),
         }
     }
 
-    /// Gets a user with caching
-    pub async fn get_user_cached(&self, id: u32) -> Result<Option<User>, UserError> {
-        // Check cache first
-        {
-            let cache = self.cache.read().await;
-            if let Some(user) = cache.get(&id) {
-                return Ok(Some(user.clone()));
-            }
-        }
+    /// Builds a semaphore sized by `config.max_connections`
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.max_connections)
+    }
 
-        // Fetch from service
-        if let Some(user) = self.service.get_user(id).await? {
-            let mut cache = self.cache.write().await;
-            cache.insert(id, user.clone());
-            Ok(Some(user))
-        } else {
-            Ok(None)
+    /// Waits until a permit is free; the permit is returned when dropped
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        self.permits.acquire().await.expect("connection semaphore is never closed")
+    }
+
+    /// Returns how many operations could start right now without waiting
+    pub fn available_permits(&self) -> usize {
+        self.permits.available_permits()
+    }
+}
+
+impl AuditEntry {
+    /// Records `action` on `user_id` by `actor` as happening now
+    pub fn new(action: AuditAction, user_id: u32, actor: Option<u32>) -> Self {
+        Self {
+            action,
+            user_id,
+            at: SystemTime::now(),
+            actor,
         }
     }
+}
 
-    /// Invalidates the cache for a user
-    pub async fn invalidate_cache(&self, id: u32) {
-        let mut cache = self.cache.write().await;
-        cache.remove(&id);
+impl InMemoryAuditSink {
+    /// Creates an empty sink
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    /// Clears the entire cache
-    pub async fn clear_cache(&self) {
-        let mut cache = self.cache.write().await;
-        cache.clear();
+    /// Returns every recorded entry in the order it was recorded
+    pub fn entries(&self) -> Vec<AuditEntry> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).clone()
     }
 }
 
-/// Authentication module
-pub mod auth {
-    use super::*;
-    
-    /// JWT token claims
-    #[derive(Debug, Serialize, Deserialize)]
-    pub struct Claims {
-        pub sub: u32,
-        pub exp: usize,
-        pub iat: usize,
+impl AuditSink for InMemoryAuditSink {
+    fn record(&self, entry: AuditEntry) {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner()).push(entry);
     }
-    
-    /// Authenticates a user with email and password
-    pub async fn authenticate_user(
-        service: &dyn UserService,
-        email: &str,
-        password: &str,
-    ) -> Result<Option<User>, UserError> {
-        let users = service.list_users().await?;
-        
-        for user in users {
-            if user.email == email && user.verify_password(password) {
-                return Ok(Some(user));
-            }
-        }
-        
-        Ok(None)
+}
+
+impl AuditListener {
+    /// Creates a listener recording system-initiated mutations without an actor
+    pub fn new(sink: Arc<dyn AuditSink>) -> Self {
+        Self { sink, actor: None }
     }
-    
-    /// Generates a JWT token for a user
-    pub fn generate_token(user_id: u32, secret: &str) -> Result<String, Box<dyn Error>> {
-        // Implementation would generate JWT token
-        Ok(format!("token_for_user_{}", user_id))
+
+    /// Creates a listener attributing every mutation to `actor`
+    pub fn with_actor(sink: Arc<dyn AuditSink>, actor: u32) -> Self {
+        Self { sink, actor: Some(actor) }
     }
-    
-    /// Validates a JWT token
-    pub fn validate_token(token: &str, secret: &str) -> Result<Claims, Box<dyn Error>> {
-        // Implementation would validate JWT token
-        Ok(Claims {
-            sub: 1,
-            exp: 0,
-            iat: 0,
-        })
+}
+
+impl EventListener for AuditListener {
+    fn on_created(&self, user: &User) {
+        self.sink.record(AuditEntry::new(AuditAction::Created, user.id, current_actor().or(self.actor)));
+    }
+
+    fn on_updated(&self, user: &User) {
+        self.sink.record(AuditEntry::new(AuditAction::Updated, user.id, current_actor().or(self.actor)));
+    }
+
+    fn on_deleted(&self, id: u32) {
+        self.sink.record(AuditEntry::new(AuditAction::Deleted, id, current_actor().or(self.actor)));
     }
 }
 
-/// Utility functions
-pub mod utils {
-    use super::*;
-    
-    /// Generates a unique ID
-    pub fn generate_id() -> u32 {
-        unsafe {
-            GLOBAL_COUNTER += 1;
-            GLOBAL_COUNTER
+tokio::task_local! {
+    static CURRENT_ACTOR: u32;
+}
+
+/// Runs `f` with `actor` as the performer of any audited changes it makes, overriding a listener's fixed actor
+pub async fn as_actor<F: Future>(actor: u32, f: F) -> F::Output {
+    CURRENT_ACTOR.scope(actor, f).await
+}
+
+/// Returns the actor set by an enclosing `as_actor`, if any
+pub fn current_actor() -> Option<u32> {
+    CURRENT_ACTOR.try_with(|actor| *actor).ok()
+}
+
+impl PasswordHasher for PlaintextHasher {
+    fn hash(&self, password: &str) -> Result<String, UserError> {
+        Ok(format!("hashed_{}", password))
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+impl TestClock {
+    /// Creates a clock frozen at the current time
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            start_system: SystemTime::now(),
+            offset: Mutex::new(Duration::ZERO),
         }
     }
-    
-    /// Validates an email address
-    pub fn is_valid_email(email: &str) -> bool {
-        email.contains('@') && email.contains('.')
+
+    /// Moves the clock forward by `by`
+    pub fn advance(&self, by: Duration) {
+        *self.offset.lock().unwrap() += by;
     }
-    
-    /// Formats a user for display
-    pub fn format_user(user: &User) -> String {
-        format!("{} <{}>", user.name, user.email)
+}
+
+impl Default for TestClock {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-// Helper functions
-fn default_max_connections() -> u32 {
-    100
+impl Clock for TestClock {
+    fn now(&self) -> Instant {
+        self.start + *self.offset.lock().unwrap()
+    }
+
+    fn system_now(&self) -> SystemTime {
+        self.start_system + *self.offset.lock().unwrap()
+    }
 }
 
-fn hash_password(password: &str) -> String {
-    // In a real implementation, this would use a proper hashing algorithm
-    format!("hashed_{}", password)
+#[cfg(feature = "argon2")]
+impl Argon2Hasher {
+    /// Creates a new Argon2Hasher with the default parameters
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an Argon2id hasher with explicit memory (KiB), iteration and parallelism costs
+    pub fn with_params(memory_kib: u32, iterations: u32, parallelism: u32) -> Result<Self, UserError> {
+        let params = argon2::Params::new(memory_kib, iterations, parallelism, None)
+            .map_err(|e| UserError::Hashing(e.to_string()))?;
+        let argon2 = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+        Ok(Self {
+            argon2,
+            dummy_hash: std::sync::OnceLock::new(),
+        })
+    }
+
+    /// Creates a hasher using `config.password_cost` as the iteration count, if set
+    pub fn from_config(config: &Config) -> Result<Self, UserError> {
+        match config.password_cost {
+            Some(iterations) => Self::with_params(
+                argon2::Params::DEFAULT_M_COST,
+                iterations,
+                argon2::Params::DEFAULT_P_COST,
+            ),
+            None => Ok(Self::new()),
+        }
+    }
 }
 
-// Macros
-macro_rules! log_user_action {
-    ($action:expr, $user:expr) => {
-        log::info!("User action: {} for user {}", $action, $user.id);
-    };
+#[cfg(feature = "argon2")]
+impl PasswordHasher for Argon2Hasher {
+    fn hash(&self, password: &str) -> Result<String, UserError> {
+        use argon2::password_hash::{rand_core::OsRng, PasswordHasher as _, SaltString};
+
+        let salt = SaltString::generate(&mut OsRng);
+        self.argon2
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| UserError::Hashing(e.to_string()))
+    }
+
+    fn verify(&self, password: &str, hash: &str) -> Result<bool, UserError> {
+        use argon2::password_hash::{Error as HashError, PasswordHash, PasswordVerifier};
+
+        let parsed = PasswordHash::new(hash).map_err(|e| UserError::Hashing(e.to_string()))?;
+        match self.argon2.verify_password(password.as_bytes(), &parsed) {
+            Ok(()) => Ok(true),
+            Err(HashError::Password) => Ok(false),
+            Err(e) => Err(UserError::Hashing(e.to_string())),
+        }
+    }
+
+    /// Verifies against a hash made once with this hasher's own parameters, so the cost matches
+    fn verify_dummy(&self, password: &str) {
+        let hash = self
+            .dummy_hash
+            .get_or_init(|| self.hash(DUMMY_PASSWORD_HASH).unwrap_or_default());
+        let _ = self.verify(password, hash);
+    }
+
+    /// Flags hashes whose algorithm or cost parameters differ from this hasher's
+    fn needs_rehash(&self, hash: &str) -> bool {
+        use argon2::password_hash::PasswordHash;
+
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return false;
+        };
+        let Ok(stored) = argon2::Params::try_from(&parsed) else {
+            return false;
+        };
+        let current = self.argon2.params();
+        parsed.algorithm != argon2::Algorithm::Argon2id.ident()
+            || stored.m_cost() != current.m_cost()
+            || stored.t_cost() != current.t_cost()
+            || stored.p_cost() != current.p_cost()
+    }
 }
 
-// Tests
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl UserServiceImpl {
+    /// Creates a new UserService instance with `DEFAULT_SHARD_COUNT` shards
+    pub fn new() -> Self {
+        Self::new_with_shards(DEFAULT_SHARD_COUNT)
+    }
 
-    #[tokio::test]
-    async fn test_user_creation() {
-        let service = UserServiceImpl::new();
-        let user = User::new("John Doe".to_string(), "john@example.com".to_string());
-        
-        let created_user = service.create_user(user).await.unwrap();
-        assert_eq!(created_user.name, "John Doe");
-        assert_eq!(created_user.email, "john@example.com");
-        assert!(created_user.id > 0);
+    /// Creates a new UserService instance that spreads users over `shards` locks.
+    ///
+    /// Writes to users in different shards do not block each other; at least one shard is used.
+    pub fn new_with_shards(shards: usize) -> Self {
+        Self {
+            users: UserStore::new(shards),
+            emails: RwLock::new(HashMap::new()),
+            uuids: RwLock::new(HashMap::new()),
+            next_id: RwLock::new(1),
+            listeners: Vec::new(),
+            shutdown: CancellationToken::new(),
+            limit: None,
+        }
     }
 
-    #[test]
-    fn test_user_validation() {
-        let mut user = User::new("".to_string(), "john@example.com".to_string());
-        assert!(user.validate().is_err());
-        
-        user.name = "John".to_string();
-        assert!(user.validate().is_ok());
+    /// Creates a new UserService instance running at most `config.max_connections` operations at once.
+    ///
+    /// Operations beyond the limit wait for a permit instead of failing.
+    pub fn with_limit(config: &Config) -> Self {
+        Self {
+            limit: Some(ConnectionSemaphore::from_config(config)),
+            ..Self::new()
+        }
     }
 
-    #[test]
-    fn test_status_methods() {
-        assert!(Status::Completed.is_completed());
-        assert!(Status::Pending.is_active());
-        assert_eq!(Status::InProgress.as_str(), "in_progress");
+    /// Creates a new UserService instance that rejects writes once `shutdown` is cancelled.
+    ///
+    /// Reads keep working after shutdown so in-flight requests can drain.
+    pub fn with_shutdown(shutdown: CancellationToken) -> Self {
+        Self {
+            shutdown,
+            ..Self::new()
+        }
+    }
+
+    /// Sets the id handed to the next created user, for deterministic tests.
+    ///
+    /// The sequence never moves below an id already in use, so existing users are never overwritten.
+    pub async fn reset_sequence(&self, start: u32) {
+        let mut next_id = self.next_id.write().await;
+        let highest = self.users.collect(|_| true).await.into_iter().map(|user| user.id).max();
+        *next_id = highest.map_or(start, |id| start.max(id.saturating_add(1)));
+    }
+
+    /// Recomputes the email and uuid indexes from the stored users, soft-deleted ones included.
+    ///
+    /// Holding both index write locks keeps writers out while they are rebuilt. If two
+    /// users share an email or uuid, the lower id keeps it.
+    pub async fn rebuild_indexes(&self) -> Result<(), UserError> {
+        let _permit = self.acquire_permit().await;
+        let mut emails = self.emails.write().await;
+        let mut uuids = self.uuids.write().await;
+        let mut users = self.users.collect(|_| true).await;
+        users.sort_by_key(|user| user.id);
+
+        let mut rebuilt_emails = HashMap::with_capacity(users.len());
+        let mut rebuilt_uuids = HashMap::with_capacity(users.len());
+        for user in users {
+            match rebuilt_uuids.get(&user.uuid) {
+                Some(owner) => log::warn!("Users {} and {} share uuid {}; keeping {}", owner, user.id, user.uuid, owner),
+                None => {
+                    rebuilt_uuids.insert(user.uuid, user.id);
+                }
+            }
+            match rebuilt_emails.get(&user.email) {
+                Some(owner) => log::warn!("Users {} and {} share email {}; keeping {}", owner, user.id, user.email, owner),
+                None => {
+                    rebuilt_emails.insert(user.email, user.id);
+                }
+            }
+        }
+        *emails = rebuilt_emails;
+        *uuids = rebuilt_uuids;
+        Ok(())
+    }
+
+    /// Resolves the holder of an already registered email for `get_or_create_by_email`
+    async fn email_owner(&self, id: u32) -> Result<(User, bool), UserError> {
+        match self.users.get(id).await.filter(|existing| !existing.is_deleted()) {
+            Some(existing) => Ok((existing, false)),
+            None => Err(UserError::InvalidInput("email already registered".to_string())),
+        }
+    }
+
+    /// Like `get_user`, but shares the stored user instead of cloning it
+    pub async fn get_user_arc(&self, id: u32) -> Result<Option<Arc<User>>, UserError> {
+        let _permit = self.acquire_permit().await;
+        Ok(self.users.get_arc(id).await.filter(|user| !user.is_deleted()))
+    }
+
+    /// Permanently deletes every stored user matching `pred`, soft-deleted ones included, and returns how many were removed
+    pub async fn delete_users_where<F: Fn(&User) -> bool>(&self, pred: F) -> Result<usize, UserError> {
+        let _permit = self.acquire_permit().await;
+        self.ensure_accepting_writes()?;
+
+        let mut emails = self.emails.write().await;
+        let mut uuids = self.uuids.write().await;
+        let removed = self.users.remove_where(pred).await;
+        for user in &removed {
+            if emails.get(&user.email) == Some(&user.id) {
+                emails.remove(&user.email);
+            }
+            if uuids.get(&user.uuid) == Some(&user.id) {
+                uuids.remove(&user.uuid);
+            }
+        }
+        drop((emails, uuids));
+
+        #[cfg(feature = "metrics")]
+        metrics::counter!("users_deleted_total").increment(removed.len() as u64);
+        for user in &removed {
+            self.emit(|listener| listener.on_deleted(user.id));
+        }
+        Ok(removed.len())
+    }
+
+    /// Creates a user, attributing the audit entry to `actor`
+    pub async fn create_user_as(&self, actor: u32, user: User) -> Result<User, UserError> {
+        as_actor(actor, self.create_user(user)).await
+    }
+
+    /// Updates a user, attributing the audit entry to `actor`
+    pub async fn update_user_as(&self, actor: u32, user: User) -> Result<User, UserError> {
+        as_actor(actor, self.update_user(user)).await
+    }
+
+    /// Deletes a user, attributing the audit entry to `actor`
+    pub async fn delete_user_as(&self, actor: u32, id: u32) -> Result<(), UserError> {
+        as_actor(actor, self.delete_user(id)).await
+    }
+
+    /// Registers a listener for lifecycle events
+    pub fn add_listener(&mut self, listener: Arc<dyn EventListener>) {
+        self.listeners.push(listener);
+    }
+
+    /// Records every successful create, update and delete to `sink`
+    pub fn add_audit_sink(&mut self, sink: Arc<dyn AuditSink>) {
+        self.add_listener(Arc::new(AuditListener::new(sink)));
+    }
+
+    /// Waits for a permit when a connection limit is configured
+    async fn acquire_permit(&self) -> Option<SemaphorePermit<'_>> {
+        match &self.limit {
+            Some(limit) => Some(limit.acquire().await),
+            None => None,
+        }
+    }
+
+    /// Fails with `Unavailable` once shutdown has been signaled
+    fn ensure_accepting_writes(&self) -> Result<(), UserError> {
+        if self.shutdown.is_cancelled() {
+            return Err(UserError::Unavailable);
+        }
+        Ok(())
+    }
+
+    /// Dispatches an event to every registered listener
+    fn emit(&self, event: impl Fn(&dyn EventListener)) {
+        for listener in &self.listeners {
+            event(listener.as_ref());
+        }
+    }
+
+    /// Runs `f` against this service, restoring the stored users if it fails.
+    ///
+    /// The snapshot is taken before `f` runs, so writes made concurrently by
+    /// other callers are also rolled back on failure. Issued ids are not reused.
+    pub async fn with_transaction<'a, F, Fut, T>(&'a self, f: F) -> Result<T, UserError>
+    where
+        F: FnOnce(&'a Self) -> Fut,
+        Fut: Future<Output = Result<T, UserError>>,
+    {
+        let (users_snapshot, emails_snapshot, uuids_snapshot) = {
+            let emails = self.emails.read().await;
+            let uuids = self.uuids.read().await;
+            (self.users.snapshot().await, emails.clone(), uuids.clone())
+        };
+
+        match f(self).await {
+            Ok(value) => Ok(value),
+            Err(e) => {
+                let mut emails = self.emails.write().await;
+                let mut uuids = self.uuids.write().await;
+                self.users.restore(users_snapshot).await;
+                *emails = emails_snapshot;
+                *uuids = uuids_snapshot;
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "dashmap"))]
+impl<V: Clone> ShardedMap<V> {
+    /// Creates `shard_count` empty shards, using at least one
+    fn new(shard_count: usize) -> Self {
+        Self {
+            shards: (0..shard_count.max(1)).map(|_| RwLock::new(HashMap::new())).collect(),
+        }
+    }
+
+    /// Returns the shard that owns `id`
+    fn shard(&self, id: u32) -> &RwLock<HashMap<u32, Arc<V>>> {
+        &self.shards[id as usize % self.shards.len()]
+    }
+
+    /// Clones the value stored under `id`
+    async fn get(&self, id: u32) -> Option<V> {
+        self.get_arc(id).await.map(|value| V::clone(&value))
+    }
+
+    /// Shares the value stored under `id` without cloning it
+    async fn get_arc(&self, id: u32) -> Option<Arc<V>> {
+        self.shard(id).read().await.get(&id).cloned()
+    }
+
+    /// Clones the values stored under `ids`, in order, read-locking each shard once
+    async fn get_many(&self, ids: &[u32]) -> Vec<Option<V>> {
+        let mut values = vec![None; ids.len()];
+        for (index, shard) in self.shards.iter().enumerate() {
+            let owned: Vec<(usize, u32)> = ids
+                .iter()
+                .enumerate()
+                .filter(|(_, id)| **id as usize % self.shards.len() == index)
+                .map(|(position, id)| (position, *id))
+                .collect();
+            if owned.is_empty() {
+                continue;
+            }
+
+            let shard = shard.read().await;
+            for (position, id) in owned {
+                values[position] = shard.get(&id).map(|value| V::clone(value));
+            }
+        }
+        values
+    }
+
+    /// Stores `value` under `id`, returning the value it replaced
+    async fn insert(&self, id: u32, value: V) -> Option<V> {
+        self.shard(id).write().await.insert(id, Arc::new(value)).map(Arc::unwrap_or_clone)
+    }
+
+    /// Stores every value while holding all shard locks, so readers never see a partial batch
+    async fn insert_many(&self, values: impl IntoIterator<Item = (u32, V)>) {
+        let mut shards = self.write_all().await;
+        for (id, value) in values {
+            shards.shard_mut(id).insert(id, Arc::new(value));
+        }
+    }
+
+    /// Removes and returns the value stored under `id`
+    async fn remove(&self, id: u32) -> Option<V> {
+        self.shard(id).write().await.remove(&id).map(Arc::unwrap_or_clone)
+    }
+
+    /// Removes and returns every value matching `pred` while holding all shard locks
+    async fn remove_where(&self, pred: impl Fn(&V) -> bool) -> Vec<V> {
+        let mut shards = self.write_all().await;
+        let mut removed = Vec::new();
+        for shard in shards.guards.iter_mut() {
+            let ids: Vec<u32> = shard.iter().filter(|(_, value)| pred(value)).map(|(id, _)| *id).collect();
+            removed.extend(ids.iter().filter_map(|id| shard.remove(id)).map(Arc::unwrap_or_clone));
+        }
+        removed
+    }
+
+    /// Runs `f` on the value stored under `id` while its shard is write-locked, copying it first if shared
+    async fn modify<R>(&self, id: u32, f: impl FnOnce(&mut V) -> R) -> Option<R> {
+        self.shard(id).write().await.get_mut(&id).map(|value| f(Arc::make_mut(value)))
+    }
+
+    /// Clones every value matching `pred`, locking one shard at a time
+    async fn collect(&self, pred: impl Fn(&V) -> bool) -> Vec<V> {
+        let mut values = Vec::new();
+        for shard in &self.shards {
+            let shard = shard.read().await;
+            values.extend(shard.values().filter(|value| pred(value)).map(|value| V::clone(value)));
+        }
+        values
+    }
+
+    /// Counts values matching `pred` without cloning them
+    async fn count(&self, pred: impl Fn(&V) -> bool) -> usize {
+        let mut count = 0;
+        for shard in &self.shards {
+            count += shard.read().await.values().filter(|value| pred(value)).count();
+        }
+        count
+    }
+
+    /// Write-locks every shard in order, for operations spanning many ids
+    async fn write_all(&self) -> ShardsWriteGuard<'_, V> {
+        let mut guards = Vec::with_capacity(self.shards.len());
+        for shard in &self.shards {
+            guards.push(shard.write().await);
+        }
+        ShardsWriteGuard { guards }
+    }
+
+    /// Clones the contents of every shard at a single point in time
+    async fn snapshot(&self) -> Vec<HashMap<u32, Arc<V>>> {
+        self.write_all().await.snapshot()
+    }
+
+    /// Replaces the contents of every shard with a previous snapshot
+    async fn restore(&self, snapshot: Vec<HashMap<u32, Arc<V>>>) {
+        self.write_all().await.restore(snapshot);
+    }
+}
+
+#[cfg(not(feature = "dashmap"))]
+impl<V: Clone> ShardsWriteGuard<'_, V> {
+    /// Returns the locked shard that owns `id`
+    fn shard_mut(&mut self, id: u32) -> &mut HashMap<u32, Arc<V>> {
+        let shard_count = self.guards.len();
+        &mut self.guards[id as usize % shard_count]
+    }
+
+    /// Clones the contents of every shard, sharing the values themselves
+    fn snapshot(&self) -> Vec<HashMap<u32, Arc<V>>> {
+        self.guards.iter().map(|shard| (**shard).clone()).collect()
+    }
+
+    /// Replaces the contents of every shard with a previous snapshot
+    fn restore(&mut self, snapshot: Vec<HashMap<u32, Arc<V>>>) {
+        for (shard, contents) in self.guards.iter_mut().zip(snapshot) {
+            **shard = contents;
+        }
+    }
+}
+
+#[cfg(feature = "dashmap")]
+impl<V: Clone> DashMapStore<V> {
+    /// Creates a map with at least `shard_count` shards, rounded up to a power of two
+    fn new(shard_count: usize) -> Self {
+        Self {
+            map: dashmap::DashMap::with_shard_amount(shard_count.max(2).next_power_of_two()),
+        }
+    }
+
+    /// Clones the value stored under `id`
+    async fn get(&self, id: u32) -> Option<V> {
+        self.map.get(&id).map(|value| V::clone(&value))
+    }
+
+    /// Shares the value stored under `id` without cloning it
+    async fn get_arc(&self, id: u32) -> Option<Arc<V>> {
+        self.map.get(&id).map(|value| Arc::clone(&value))
+    }
+
+    /// Clones the values stored under `ids`, in order
+    async fn get_many(&self, ids: &[u32]) -> Vec<Option<V>> {
+        ids.iter().map(|id| self.map.get(id).map(|value| V::clone(&value))).collect()
+    }
+
+    /// Stores `value` under `id`, returning the value it replaced
+    async fn insert(&self, id: u32, value: V) -> Option<V> {
+        self.map.insert(id, Arc::new(value)).map(Arc::unwrap_or_clone)
+    }
+
+    /// Stores every value; readers may observe the batch partially applied
+    async fn insert_many(&self, values: impl IntoIterator<Item = (u32, V)>) {
+        for (id, value) in values {
+            self.map.insert(id, Arc::new(value));
+        }
+    }
+
+    /// Removes and returns the value stored under `id`
+    async fn remove(&self, id: u32) -> Option<V> {
+        self.map.remove(&id).map(|(_, value)| Arc::unwrap_or_clone(value))
+    }
+
+    /// Removes and returns every value matching `pred`; readers may observe the removal partially applied
+    async fn remove_where(&self, pred: impl Fn(&V) -> bool) -> Vec<V> {
+        let ids: Vec<u32> = self.map.iter().filter(|entry| pred(entry.value())).map(|entry| *entry.key()).collect();
+        ids.iter()
+            .filter_map(|id| self.map.remove_if(id, |_, value| pred(value)).map(|(_, value)| Arc::unwrap_or_clone(value)))
+            .collect()
+    }
+
+    /// Runs `f` on the value stored under `id` while its shard is write-locked, copying it first if shared
+    async fn modify<R>(&self, id: u32, f: impl FnOnce(&mut V) -> R) -> Option<R> {
+        self.map.get_mut(&id).map(|mut value| f(Arc::make_mut(value.value_mut())))
+    }
+
+    /// Clones every value matching `pred`
+    async fn collect(&self, pred: impl Fn(&V) -> bool) -> Vec<V> {
+        self.map
+            .iter()
+            .filter(|entry| pred(entry.value()))
+            .map(|entry| V::clone(entry.value()))
+            .collect()
+    }
+
+    /// Counts values matching `pred` without cloning them
+    async fn count(&self, pred: impl Fn(&V) -> bool) -> usize {
+        self.map.iter().filter(|entry| pred(entry.value())).count()
+    }
+
+    /// Clones every entry, sharing the values themselves
+    async fn snapshot(&self) -> Vec<(u32, Arc<V>)> {
+        self.map.iter().map(|entry| (*entry.key(), Arc::clone(entry.value()))).collect()
+    }
+
+    /// Replaces the contents of the map with a previous snapshot
+    async fn restore(&self, snapshot: Vec<(u32, Arc<V>)>) {
+        self.map.clear();
+        for (id, value) in snapshot {
+            self.map.insert(id, value);
+        }
+    }
+}
+
+#[async_trait]
+impl UserService for UserServiceImpl {
+    async fn get_user(&self, id: u32) -> Result<Option<User>, UserError> {
+        let _permit = self.acquire_permit().await;
+        Ok(self.users.get(id).await.filter(|user| !user.is_deleted()))
+    }
+
+    async fn get_users(&self, ids: &[u32]) -> Result<Vec<Option<User>>, UserError> {
+        let _permit = self.acquire_permit().await;
+        let mut users = self.users.get_many(ids).await;
+        for user in &mut users {
+            if user.as_ref().is_some_and(User::is_deleted) {
+                *user = None;
+            }
+        }
+        Ok(users)
+    }
+
+    async fn get_user_by_uuid(&self, uuid: Uuid) -> Result<Option<User>, UserError> {
+        let _permit = self.acquire_permit().await;
+        let id = match self.uuids.read().await.get(&uuid) {
+            Some(id) => *id,
+            None => return Ok(None),
+        };
+
+        Ok(self
+            .users
+            .get(id)
+            .await
+            .filter(|user| user.uuid == uuid && !user.is_deleted()))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, user), fields(id = tracing::field::Empty)))]
+    async fn create_user(&self, mut user: User) -> Result<User, UserError> {
+        let _permit = self.acquire_permit().await;
+        self.ensure_accepting_writes()?;
+        user.email = user.email_normalized();
+        user.validate()?;
+        
+        let mut next_id = self.next_id.write().await;
+        let mut emails = self.emails.write().await;
+        if emails.contains_key(&user.email) {
+            return Err(UserError::InvalidInput("email already registered".to_string()));
+        }
+        let mut uuids = self.uuids.write().await;
+
+        let id = *next_id;
+        *next_id += 1;
+        user.id = id;
+        user.uuid = Uuid::new_v4();
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("id", id);
+        user.version = 0;
+        user.created_at = SystemTime::now();
+        user.updated_at = user.created_at;
+        
+        self.users.insert(id, user.clone()).await;
+        emails.insert(user.email.clone(), id);
+        uuids.insert(user.uuid, id);
+        drop((next_id, emails, uuids));
+        
+        #[cfg(feature = "metrics")]
+        metrics::counter!("users_created_total").increment(1);
+        self.emit(|listener| listener.on_created(&user));
+        Ok(user)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self, user), fields(id = user.id)))]
+    async fn update_user(&self, mut user: User) -> Result<User, UserError> {
+        let _permit = self.acquire_permit().await;
+        self.ensure_accepting_writes()?;
+        user.email = user.email_normalized();
+        user.validate()?;
+        
+        let mut emails = self.emails.write().await;
+        if emails.get(&user.email).is_some_and(|&owner| owner != user.id) {
+            return Err(UserError::InvalidInput("email already registered".to_string()));
+        }
+        let user = self
+            .users
+            .modify(user.id, |existing| {
+                if existing.is_deleted() {
+                    return Err(UserError::NotFound);
+                }
+                if existing.version != user.version {
+                    return Err(UserError::Conflict);
+                }
+                user.version += 1;
+                user.uuid = existing.uuid;
+                user.created_at = existing.created_at;
+                user.updated_at = SystemTime::now();
+
+                if emails.get(&existing.email) == Some(&user.id) {
+                    emails.remove(&existing.email);
+                }
+                emails.insert(user.email.clone(), user.id);
+                *existing = user.clone();
+                Ok(user)
+            })
+            .await
+            .unwrap_or(Err(UserError::NotFound))?;
+        drop(emails);
+
+        self.emit(|listener| listener.on_updated(&user));
+        Ok(user)
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self)))]
+    async fn delete_user(&self, id: u32) -> Result<(), UserError> {
+        let _permit = self.acquire_permit().await;
+        self.ensure_accepting_writes()?;
+        let mut emails = self.emails.write().await;
+        let mut uuids = self.uuids.write().await;
+        let removed = self.users.remove(id).await.ok_or(UserError::NotFound)?;
+
+        if emails.get(&removed.email) == Some(&id) {
+            emails.remove(&removed.email);
+        }
+        if uuids.get(&removed.uuid) == Some(&id) {
+            uuids.remove(&removed.uuid);
+        }
+        drop((emails, uuids));
+
+        #[cfg(feature = "metrics")]
+        metrics::counter!("users_deleted_total").increment(1);
+        self.emit(|listener| listener.on_deleted(id));
+        Ok(())
+    }
+
+    /// Lists active users in id order
+    async fn list_users(&self) -> Result<Vec<User>, UserError> {
+        let _permit = self.acquire_permit().await;
+        let mut sorted = self.users.collect(|user| !user.is_deleted()).await;
+        sorted.sort_by_key(|user| user.id);
+        Ok(sorted)
+    }
+
+    async fn list_users_paged(&self, offset: usize, limit: usize) -> Result<Page<User>, UserError> {
+        let _permit = self.acquire_permit().await;
+        let mut sorted = self.users.collect(|user| !user.is_deleted()).await;
+        sorted.sort_by_key(|user| user.id);
+
+        let total = sorted.len();
+        let items: Vec<User> = sorted.into_iter().skip(offset).take(limit).collect();
+        let has_more = offset.saturating_add(items.len()) < total;
+
+        Ok(Page { items, total, has_more })
+    }
+
+    /// Keyset pagination: returns up to `limit` users with an id greater than `after_id`
+    async fn list_users_after(&self, after_id: Option<u32>, limit: usize) -> Result<Vec<User>, UserError> {
+        let _permit = self.acquire_permit().await;
+        let after_id = after_id.unwrap_or(0);
+        let mut sorted = self
+            .users
+            .collect(|user| !user.is_deleted() && user.id > after_id)
+            .await;
+        sorted.sort_by_key(|user| user.id);
+        sorted.truncate(limit);
+        Ok(sorted)
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, UserError> {
+        let _permit = self.acquire_permit().await;
+        let email = utils::normalize_email(email);
+        let id = match self.emails.read().await.get(&email) {
+            Some(id) => *id,
+            None => return Ok(None),
+        };
+
+        Ok(self
+            .users
+            .get(id)
+            .await
+            .filter(|user| user.email == email && !user.is_deleted()))
+    }
+
+    async fn create_users(&self, users: Vec<User>) -> Result<BatchResult, UserError> {
+        let _permit = self.acquire_permit().await;
+        self.ensure_accepting_writes()?;
+        let mut result = BatchResult::default();
+
+        let mut next_id = self.next_id.write().await;
+        let mut emails = self.emails.write().await;
+        let mut uuids = self.uuids.write().await;
+        let mut pending = HashMap::new();
+
+        for (index, mut user) in users.into_iter().enumerate() {
+            user.email = user.email_normalized();
+            if let Err(e) = user.validate() {
+                result.failed.push((index, e));
+                continue;
+            }
+            if emails.contains_key(&user.email) || pending.contains_key(&user.email) {
+                result
+                    .failed
+                    .push((index, UserError::InvalidInput("email already registered".to_string())));
+                continue;
+            }
+
+            user.id = *next_id;
+            user.uuid = Uuid::new_v4();
+            user.version = 0;
+            user.created_at = SystemTime::now();
+            user.updated_at = user.created_at;
+            *next_id += 1;
+            pending.insert(user.email.clone(), user.id);
+            result.created.push(user);
+        }
+        self.users
+            .insert_many(result.created.iter().map(|user| (user.id, user.clone())))
+            .await;
+        emails.extend(pending);
+        uuids.extend(result.created.iter().map(|user| (user.uuid, user.id)));
+        drop((next_id, emails, uuids));
+
+        #[cfg(feature = "metrics")]
+        metrics::counter!("users_created_total").increment(result.created.len() as u64);
+        for user in &result.created {
+            self.emit(|listener| listener.on_created(user));
+        }
+        Ok(result)
+    }
+
+    async fn soft_delete_user(&self, id: u32) -> Result<(), UserError> {
+        let _permit = self.acquire_permit().await;
+        self.ensure_accepting_writes()?;
+        self.users
+            .modify(id, |user| {
+                if user.is_deleted() {
+                    return Err(UserError::NotFound);
+                }
+                user.deleted_at = Some(SystemTime::now());
+                Ok(())
+            })
+            .await
+            .unwrap_or(Err(UserError::NotFound))?;
+
+        #[cfg(feature = "metrics")]
+        metrics::counter!("users_deleted_total").increment(1);
+        self.emit(|listener| listener.on_deleted(id));
+        Ok(())
+    }
+
+    async fn list_users_including_deleted(&self) -> Result<Vec<User>, UserError> {
+        let _permit = self.acquire_permit().await;
+        let mut sorted = self.users.collect(|_| true).await;
+        sorted.sort_by_key(|user| user.id);
+        Ok(sorted)
+    }
+
+    async fn search_users(&self, query: UserQuery) -> Result<Vec<User>, UserError> {
+        let _permit = self.acquire_permit().await;
+        let matches = self
+            .users
+            .collect(|user| !user.is_deleted() && query.matches(user))
+            .await;
+        Ok(query.order_and_page(matches))
+    }
+
+    async fn count_users(&self) -> Result<usize, UserError> {
+        let _permit = self.acquire_permit().await;
+        Ok(self.users.count(|user| !user.is_deleted()).await)
+    }
+
+    async fn count_all(&self) -> Result<usize, UserError> {
+        let _permit = self.acquire_permit().await;
+        Ok(self.users.count(|_| true).await)
+    }
+
+    async fn register_user(&self, new_user: NewUser, hasher: &dyn PasswordHasher) -> Result<User, UserError> {
+        let user = new_user.into_user(hasher)?;
+        self.create_user(user).await
+    }
+
+    /// Returns the active user holding the email, or creates one; `true` means it was created.
+    ///
+    /// The password is only validated and hashed when no user holds the email. The final
+    /// lookup and insert share the `emails` write lock, so concurrent callers can't both create.
+    async fn get_or_create_by_email(
+        &self,
+        new_user: NewUser,
+        hasher: &dyn PasswordHasher,
+    ) -> Result<(User, bool), UserError> {
+        let _permit = self.acquire_permit().await;
+        self.ensure_accepting_writes()?;
+        let email = utils::normalize_email(&new_user.email);
+        let owner = self.emails.read().await.get(&email).copied();
+        if let Some(id) = owner {
+            return self.email_owner(id).await;
+        }
+
+        let mut user = new_user.into_user(hasher)?;
+        user.email = email;
+
+        let mut next_id = self.next_id.write().await;
+        let mut emails = self.emails.write().await;
+        if let Some(&id) = emails.get(&user.email) {
+            return self.email_owner(id).await;
+        }
+        let mut uuids = self.uuids.write().await;
+
+        let id = *next_id;
+        *next_id += 1;
+        user.id = id;
+        user.uuid = Uuid::new_v4();
+        user.version = 0;
+        user.created_at = SystemTime::now();
+        user.updated_at = user.created_at;
+
+        self.users.insert(id, user.clone()).await;
+        emails.insert(user.email.clone(), id);
+        uuids.insert(user.uuid, id);
+        drop((next_id, emails, uuids));
+
+        #[cfg(feature = "metrics")]
+        metrics::counter!("users_created_total").increment(1);
+        self.emit(|listener| listener.on_created(&user));
+        Ok((user, true))
+    }
+
+    /// Moves a user to `new_email`, failing with `InvalidInput` if another user holds it
+    async fn change_email(&self, id: u32, new_email: String) -> Result<User, UserError> {
+        let _permit = self.acquire_permit().await;
+        self.ensure_accepting_writes()?;
+        let new_email = utils::normalize_email(&new_email);
+        if !utils::is_valid_email(&new_email) {
+            return Err(UserError::InvalidInput("Invalid email format".to_string()));
+        }
+
+        let mut emails = self.emails.write().await;
+        let updated = self
+            .users
+            .modify(id, |user| {
+                if user.is_deleted() {
+                    return Err(UserError::NotFound);
+                }
+                if emails.get(&new_email).is_some_and(|&owner| owner != id) {
+                    return Err(UserError::InvalidInput("Email already exists".to_string()));
+                }
+
+                if emails.get(&user.email) == Some(&id) {
+                    emails.remove(&user.email);
+                }
+                emails.insert(new_email.clone(), id);
+                user.email = new_email;
+                user.version += 1;
+                user.updated_at = SystemTime::now();
+                Ok(user.clone())
+            })
+            .await
+            .unwrap_or(Err(UserError::NotFound))?;
+        drop(emails);
+
+        self.emit(|listener| listener.on_updated(&updated));
+        Ok(updated)
+    }
+
+    /// The in-memory store has no backend to lose, so it is always healthy
+    async fn health_check(&self) -> Result<(), UserError> {
+        let _permit = self.acquire_permit().await;
+        Ok(())
+    }
+
+    async fn list_users_by_status(&self, status: Status) -> Result<Vec<User>, UserError> {
+        let _permit = self.acquire_permit().await;
+        let mut matches = self
+            .users
+            .collect(|user| !user.is_deleted() && user.status == status)
+            .await;
+        matches.sort_by_key(|user| user.id);
+        Ok(matches)
+    }
+
+    /// Applies the set fields, validates the result and bumps the version
+    async fn patch_user(&self, id: u32, patch: UserPatch) -> Result<User, UserError> {
+        let _permit = self.acquire_permit().await;
+        self.ensure_accepting_writes()?;
+
+        let mut emails = self.emails.write().await;
+        let patched = self
+            .users
+            .modify(id, |existing| {
+                if existing.is_deleted() {
+                    return Err(UserError::NotFound);
+                }
+                let mut user = existing.clone();
+                patch.apply_to(&mut user);
+                user.validate()?;
+                if emails.get(&user.email).is_some_and(|&owner| owner != id) {
+                    return Err(UserError::InvalidInput("email already registered".to_string()));
+                }
+
+                if emails.get(&existing.email) == Some(&id) {
+                    emails.remove(&existing.email);
+                }
+                emails.insert(user.email.clone(), id);
+                user.version += 1;
+                user.updated_at = SystemTime::now();
+                *existing = user.clone();
+                Ok(user)
+            })
+            .await
+            .unwrap_or(Err(UserError::NotFound))?;
+        drop(emails);
+
+        self.emit(|listener| listener.on_updated(&patched));
+        Ok(patched)
+    }
+
+    /// Hashes outside the store lock, then swaps the hash in and bumps the version
+    async fn set_user_password(
+        &self,
+        id: u32,
+        new_password: &str,
+        hasher: &dyn PasswordHasher,
+    ) -> Result<(), UserError> {
+        let _permit = self.acquire_permit().await;
+        self.ensure_accepting_writes()?;
+
+        auth::validate_password_strength(new_password)?;
+        let password_hash = hasher.hash(new_password)?;
+        let updated = self
+            .users
+            .modify(id, |user| {
+                if user.is_deleted() {
+                    return Err(UserError::NotFound);
+                }
+                user.password_hash = password_hash;
+                user.version += 1;
+                user.updated_at = SystemTime::now();
+                Ok(user.clone())
+            })
+            .await
+            .unwrap_or(Err(UserError::NotFound))?;
+
+        self.emit(|listener| listener.on_updated(&updated));
+        Ok(())
+    }
+
+    async fn mark_email_verified(&self, id: u32) -> Result<User, UserError> {
+        let _permit = self.acquire_permit().await;
+        self.ensure_accepting_writes()?;
+
+        let updated = self
+            .users
+            .modify(id, |user| {
+                if user.is_deleted() {
+                    return Err(UserError::NotFound);
+                }
+                user.email_verified = true;
+                user.version += 1;
+                user.updated_at = SystemTime::now();
+                Ok(user.clone())
+            })
+            .await
+            .unwrap_or(Err(UserError::NotFound))?;
+
+        self.emit(|listener| listener.on_updated(&updated));
+        Ok(updated)
+    }
+
+    /// Enables or disables a user; disabled users keep their data but can't log in
+    async fn set_active(&self, id: u32, active: bool) -> Result<User, UserError> {
+        let _permit = self.acquire_permit().await;
+        self.ensure_accepting_writes()?;
+
+        let updated = self
+            .users
+            .modify(id, |user| {
+                if user.is_deleted() {
+                    return Err(UserError::NotFound);
+                }
+                user.active = active;
+                user.version += 1;
+                user.updated_at = SystemTime::now();
+                Ok(user.clone())
+            })
+            .await
+            .unwrap_or(Err(UserError::NotFound))?;
+
+        self.emit(|listener| listener.on_updated(&updated));
+        Ok(updated)
+    }
+}
+
+/// The in-memory store has no replicas, so reads and writes share it
+impl ReadRouter for UserServiceImpl {
+    type Handle = Self;
+
+    fn writer(&self) -> &Self {
+        self
+    }
+}
+
+#[async_trait]
+impl Repository<u32, User> for UserServiceImpl {
+    async fn get(&self, key: &u32) -> Result<Option<User>, UserError> {
+        self.get_user(*key).await
+    }
+
+    /// Stores `value` under `key`, replacing any existing user with that id but keeping its uuid
+    async fn insert(&self, key: u32, mut value: User) -> Result<(), UserError> {
+        let _permit = self.acquire_permit().await;
+        self.ensure_accepting_writes()?;
+        value.id = key;
+        value.email = value.email_normalized();
+        value.validate()?;
+
+        let mut next_id = self.next_id.write().await;
+        let mut emails = self.emails.write().await;
+        if emails.get(&value.email).is_some_and(|&owner| owner != key) {
+            return Err(UserError::InvalidInput("email already registered".to_string()));
+        }
+        let mut uuids = self.uuids.write().await;
+
+        value.updated_at = SystemTime::now();
+        let previous = self.users.get(key).await;
+        match &previous {
+            Some(previous) => {
+                value.uuid = previous.uuid;
+                value.created_at = previous.created_at;
+            }
+            None if uuids.get(&value.uuid).is_some_and(|&owner| owner != key) => {
+                return Err(UserError::InvalidInput("uuid already registered".to_string()));
+            }
+            None => value.created_at = value.updated_at,
+        }
+        let email = value.email.clone();
+        let uuid = value.uuid;
+        self.users.insert(key, value).await;
+
+        if let Some(previous) = previous {
+            if emails.get(&previous.email) == Some(&key) {
+                emails.remove(&previous.email);
+            }
+        }
+        emails.insert(email, key);
+        uuids.insert(uuid, key);
+        *next_id = (*next_id).max(key.saturating_add(1));
+        Ok(())
+    }
+
+    async fn remove(&self, key: &u32) -> Result<Option<User>, UserError> {
+        let _permit = self.acquire_permit().await;
+        self.ensure_accepting_writes()?;
+        let mut emails = self.emails.write().await;
+        let mut uuids = self.uuids.write().await;
+        let removed = self.users.remove(*key).await;
+
+        if let Some(user) = &removed {
+            if emails.get(&user.email) == Some(key) {
+                emails.remove(&user.email);
+            }
+            if uuids.get(&user.uuid) == Some(key) {
+                uuids.remove(&user.uuid);
+            }
+        }
+        Ok(removed)
+    }
+
+    async fn list(&self) -> Result<Vec<User>, UserError> {
+        self.list_users().await
+    }
+}
+
+impl Config {
+    /// Starts building a configuration from defaults
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+
+    /// Loads configuration from environment variables
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    /// Parses and validates configuration from semicolon-separated `KEY=value` pairs.
+    ///
+    /// Keys use the environment variable names, matched case-insensitively; unset keys take their defaults.
+    pub fn from_kv_str(contents: &str) -> Result<Self, ConfigError> {
+        let malformed = |message: String| ConfigError::Parse { format: "key=value", message };
+
+        let mut vars = HashMap::new();
+        for pair in contents.split(';').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| malformed(format!("expected KEY=value, found {:?}", pair)))?;
+            let key = key.trim().to_ascii_uppercase();
+            let Some(name) = CONFIG_VARS.iter().find(|name| **name == key) else {
+                return Err(malformed(format!("unknown key {:?}", key)));
+            };
+            vars.insert(*name, value.trim().to_string());
+        }
+
+        let config = Self::from_vars(|name| vars.get(name).cloned())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Builds configuration from `lookup`, which resolves a variable name to its value if set
+    fn from_vars(lookup: impl Fn(&'static str) -> Option<String>) -> Result<Self, ConfigError> {
+        let port = Self::parse_var("PORT", lookup("PORT").unwrap_or_else(|| DEFAULT_PORT.to_string()))?;
+            
+        let database_url = lookup("DATABASE_URL")
+            .ok_or(ConfigError::MissingVar("DATABASE_URL"))?;
+            
+        let jwt_secret = lookup("JWT_SECRET")
+            .ok_or(ConfigError::MissingVar("JWT_SECRET"))?;
+            
+        let max_connections = Self::parse_var(
+            "MAX_CONNECTIONS",
+            lookup("MAX_CONNECTIONS").unwrap_or_else(|| default_max_connections().to_string()),
+        )?;
+
+        let password_cost = lookup("PASSWORD_COST")
+            .map(|cost| Self::parse_var("PASSWORD_COST", cost))
+            .transpose()?;
+
+        let access_token_ttl_secs = lookup("ACCESS_TOKEN_TTL_SECS")
+            .map_or(Ok(default_access_token_ttl_secs()), |secs| Self::parse_var("ACCESS_TOKEN_TTL_SECS", secs))?;
+
+        let refresh_token_ttl_secs = lookup("REFRESH_TOKEN_TTL_SECS")
+            .map_or(Ok(default_refresh_token_ttl_secs()), |secs| Self::parse_var("REFRESH_TOKEN_TTL_SECS", secs))?;
+
+        let read_replica_url = lookup("READ_REPLICA_URL");
+
+        Ok(Config {
+            port,
+            database_url,
+            jwt_secret,
+            max_connections,
+            password_cost,
+            access_token_ttl_secs,
+            refresh_token_ttl_secs,
+            read_replica_url,
+        })
+    }
+
+    /// Parses the value of configuration variable `name`
+    fn parse_var<T: std::str::FromStr>(name: &'static str, value: String) -> Result<T, ConfigError>
+    where
+        T::Err: Display,
+    {
+        value.parse().map_err(|e: T::Err| ConfigError::InvalidVar(name, e.to_string()))
+    }
+
+    /// Validates the configuration
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.port == 0 {
+            return Err(ConfigError::Invalid("Port must be greater than 0".to_string()));
+        }
+        
+        if self.database_url.is_empty() {
+            return Err(ConfigError::Invalid("Database URL cannot be empty".to_string()));
+        }
+        
+        if self.jwt_secret.len() < 32 {
+            return Err(ConfigError::Invalid("JWT secret must be at least 32 characters".to_string()));
+        }
+
+        if self.max_connections == 0 {
+            return Err(ConfigError::Invalid("Max connections must be at least 1".to_string()));
+        }
+
+        if self.password_cost == Some(0) {
+            return Err(ConfigError::Invalid("Password cost must be greater than 0".to_string()));
+        }
+
+        if self.access_token_ttl_secs == 0 || self.refresh_token_ttl_secs == 0 {
+            return Err(ConfigError::Invalid("Token TTLs must be greater than 0".to_string()));
+        }
+
+        let max_ttl_secs = auth::MAX_TOKEN_TTL.as_secs();
+        if self.access_token_ttl_secs > max_ttl_secs || self.refresh_token_ttl_secs > max_ttl_secs {
+            return Err(ConfigError::Invalid(format!("Token TTLs must be at most {} seconds", max_ttl_secs)));
+        }
+
+        if self.read_replica_url.as_deref() == Some("") {
+            return Err(ConfigError::Invalid("Read replica URL cannot be empty".to_string()));
+        }
+        
+        Ok(())
+    }
+
+    /// Returns how long issued access tokens stay valid
+    pub fn access_token_ttl(&self) -> Duration {
+        Duration::from_secs(self.access_token_ttl_secs)
+    }
+
+    /// Returns how long issued refresh tokens stay valid
+    pub fn refresh_token_ttl(&self) -> Duration {
+        Duration::from_secs(self.refresh_token_ttl_secs)
+    }
+
+    /// Loads and validates configuration from a TOML file
+    pub fn from_toml_file(path: &Path) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_toml_str(&contents)
+    }
+
+    /// Parses and validates configuration from a TOML string
+    pub fn from_toml_str(contents: &str) -> Result<Self, ConfigError> {
+        let config: Config = toml::from_str(contents)
+            .map_err(|e| ConfigError::Parse { format: "TOML", message: e.to_string() })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parses and validates configuration from a JSON string
+    pub fn from_json_str(contents: &str) -> Result<Self, ConfigError> {
+        let config: Config = serde_json::from_str(contents)
+            .map_err(|e| ConfigError::Parse { format: "JSON", message: e.to_string() })?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Serializes the configuration as JSON, omitting `jwt_secret`
+    pub fn to_json_string(&self) -> Result<String, ConfigError> {
+        serde_json::to_string(self).map_err(|e| ConfigError::Invalid(e.to_string()))
+    }
+
+    /// Watches a TOML config file, publishing every valid change to the receiver
+    pub fn watch_file(
+        path: impl Into<PathBuf>,
+    ) -> Result<(ConfigWatcher, watch::Receiver<Arc<Config>>), ConfigError> {
+        Self::watch_file_with_interval(path, CONFIG_POLL_INTERVAL)
+    }
+
+    /// Watches a TOML config file, checking for changes every `interval`.
+    ///
+    /// Invalid reloads are logged and skipped so the receiver keeps the last good config.
+    pub fn watch_file_with_interval(
+        path: impl Into<PathBuf>,
+        interval: Duration,
+    ) -> Result<(ConfigWatcher, watch::Receiver<Arc<Config>>), ConfigError> {
+        let path = path.into();
+        let mut last_contents = std::fs::read_to_string(&path)?;
+        let (tx, rx) = watch::channel(Arc::new(Self::from_toml_str(&last_contents)?));
+
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+
+                let contents = match std::fs::read_to_string(&path) {
+                    Ok(contents) => contents,
+                    Err(e) => {
+                        log::error!("Failed to read config {}: {}", path.display(), e);
+                        continue;
+                    }
+                };
+                if contents == last_contents {
+                    continue;
+                }
+
+                match Self::from_toml_str(&contents) {
+                    Ok(config) => {
+                        if tx.send(Arc::new(config)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => log::error!("Ignoring invalid config reload from {}: {}", path.display(), e),
+                }
+                last_contents = contents;
+            }
+        });
+
+        Ok((ConfigWatcher { task }, rx))
+    }
+}
+
+impl UserActionEvent {
+    /// Records `action` on `user_id` as happening now
+    pub fn new(action: &'static str, user_id: u32) -> Self {
+        Self {
+            action,
+            user_id,
+            at: SystemTime::now(),
+        }
+    }
+
+    /// Emits the event on the `user_action` target with each field as a key-value pair
+    pub fn log(&self) {
+        log::info!(
+            target: "user_action",
+            action = self.action,
+            user_id = self.user_id,
+            at:% = humantime::format_rfc3339(self.at);
+            "User action: {} for user {}", self.action, self.user_id
+        );
+    }
+}
+
+impl ConfigBuilder {
+    /// Sets the port, defaulting to `DEFAULT_PORT`
+    pub fn port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    /// Sets the database URL
+    pub fn database_url(mut self, database_url: impl Into<String>) -> Self {
+        self.database_url = Some(database_url.into());
+        self
+    }
+
+    /// Sets the JWT secret
+    pub fn jwt_secret(mut self, jwt_secret: impl Into<String>) -> Self {
+        self.jwt_secret = Some(jwt_secret.into());
+        self
+    }
+
+    /// Sets the connection pool size, defaulting to 100
+    pub fn max_connections(mut self, max_connections: u32) -> Self {
+        self.max_connections = Some(max_connections);
+        self
+    }
+
+    /// Sets the password hashing cost, leaving the hasher default when unset
+    pub fn password_cost(mut self, password_cost: u32) -> Self {
+        self.password_cost = Some(password_cost);
+        self
+    }
+
+    /// Sets the access token lifetime, defaulting to 15 minutes
+    pub fn access_token_ttl_secs(mut self, secs: u64) -> Self {
+        self.access_token_ttl_secs = Some(secs);
+        self
+    }
+
+    /// Sets the refresh token lifetime, defaulting to 30 days
+    pub fn refresh_token_ttl_secs(mut self, secs: u64) -> Self {
+        self.refresh_token_ttl_secs = Some(secs);
+        self
+    }
+
+    /// Sets a read replica URL, leaving reads on the primary when unset
+    pub fn read_replica_url(mut self, url: impl Into<String>) -> Self {
+        self.read_replica_url = Some(url.into());
+        self
+    }
+
+    /// Fills in defaults and validates the configuration
+    pub fn build(self) -> Result<Config, ConfigError> {
+        let config = Config {
+            port: self.port.unwrap_or(DEFAULT_PORT),
+            database_url: self.database_url.unwrap_or_default(),
+            jwt_secret: self.jwt_secret.unwrap_or_default(),
+            max_connections: self.max_connections.unwrap_or_else(default_max_connections),
+            password_cost: self.password_cost,
+            access_token_ttl_secs: self.access_token_ttl_secs.unwrap_or_else(default_access_token_ttl_secs),
+            refresh_token_ttl_secs: self.refresh_token_ttl_secs.unwrap_or_else(default_refresh_token_ttl_secs),
+            read_replica_url: self.read_replica_url,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Status {
+    /// Checks if the status represents a completed state
+    pub fn is_completed(&self) -> bool {
+        matches!(self, Status::Completed | Status::Failed)
+    }
+
+    /// Checks if the status represents an active state
+    pub fn is_active(&self) -> bool {
+        matches!(self, Status::Pending | Status::InProgress)
+    }
+
+    /// Returns the status as a string
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Status::Pending => "pending",
+            Status::InProgress => "in_progress",
+            Status::Completed => "completed",
+            Status::Failed => "failed",
+        }
+    }
+
+    /// Checks if moving from this status to `next` is allowed
+    pub fn can_transition_to(&self, next: Status) -> bool {
+        matches!(
+            (self, next),
+            (Status::Pending, Status::InProgress)
+                | (Status::InProgress, Status::Completed)
+                | (Status::InProgress, Status::Failed)
+                | (Status::Failed, Status::Pending)
+        )
+    }
+
+    /// Moves to `next`, failing if the transition is not allowed
+    pub fn try_transition(self, next: Status) -> Result<Status, UserError> {
+        if self.can_transition_to(next) {
+            Ok(next)
+        } else {
+            Err(UserError::InvalidInput(format!(
+                "Cannot transition from {} to {}",
+                self, next
+            )))
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for Status {
+    type Err = UserError;
+
+    /// Parses the snake_case form produced by `as_str`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Status::Pending),
+            "in_progress" => Ok(Status::InProgress),
+            "completed" => Ok(Status::Completed),
+            "failed" => Ok(Status::Failed),
+            other => Err(UserError::InvalidInput(format!("Unknown status: {}", other))),
+        }
+    }
+}
+
+/// TtlCache stores values that expire a fixed duration after insertion,
+/// optionally evicting the least-recently-used entry once at capacity
+pub struct TtlCache<K, V> {
+    entries: RwLock<LruCache<K, (V, Instant)>>,
+    ttl: Duration,
+    clock: Arc<dyn Clock>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Snapshot of cache counters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub size: usize,
+}
+
+/// UserManager provides high-level user management operations
+pub struct UserManager<T: UserService> {
+    service: T,
+    cache: TtlCache<u32, User>,
+    flush_hook: Option<FlushHook>,
+}
+
+/// CacheWarmer keeps a `UserManager` cache warmer running and stops it when dropped
+pub struct CacheWarmer {
+    task: tokio::task::JoinHandle<()>,
+}
+
+/// Async callback handed the users drained by `UserManager::flush`
+pub type FlushHook = Box<dyn Fn(Vec<User>) -> futures::future::BoxFuture<'static, Result<(), UserError>> + Send + Sync>;
+
+/// CachingRepository layers a TTL cache over any Repository
+pub struct CachingRepository<R, K, V> {
+    inner: R,
+    cache: TtlCache<K, V>,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    /// Creates an unbounded cache whose entries expire after `ttl`
+    pub fn new(ttl: Duration) -> Self {
+        Self::from_entries(LruCache::unbounded(), ttl)
+    }
+
+    /// Creates a cache holding at most `capacity` entries that expire after `ttl`
+    pub fn with_capacity(ttl: Duration, capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).unwrap_or(NonZeroUsize::MIN);
+        Self::from_entries(LruCache::new(capacity), ttl)
+    }
+
+    /// Creates a cache holding at most `capacity` entries that expire after `ttl`, as measured by `clock`
+    pub fn with_clock(ttl: Duration, capacity: usize, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            ..Self::with_capacity(ttl, capacity)
+        }
+    }
+
+    /// Wraps `entries` with zeroed hit/miss counters, timed by the system clock
+    fn from_entries(entries: LruCache<K, (V, Instant)>, ttl: Duration) -> Self {
+        Self {
+            entries: RwLock::new(entries),
+            ttl,
+            clock: Arc::new(SystemClock),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Gets a fresh value and marks it recently used, counting the lookup as a hit or miss
+    pub async fn get(&self, key: &K) -> Option<V> {
+        let now = self.clock.now();
+        let mut entries = self.entries.write().await;
+        match entries.get(key) {
+            Some((value, cached_at)) if now.saturating_duration_since(*cached_at) < self.ttl => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(value.clone())
+            }
+            _ => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Inserts or refreshes a value, evicting the least-recently-used entry when full
+    pub async fn insert(&self, key: K, value: V) {
+        let now = self.clock.now();
+        let mut entries = self.entries.write().await;
+        entries.put(key, (value, now));
+    }
+
+    /// Removes a value
+    pub async fn remove(&self, key: &K) {
+        let mut entries = self.entries.write().await;
+        entries.pop(key);
+    }
+
+    /// Removes every value
+    pub async fn clear(&self) {
+        let mut entries = self.entries.write().await;
+        entries.clear();
+    }
+
+    /// Removes and returns every value, including ones past their TTL
+    pub async fn drain(&self) -> Vec<(K, V)> {
+        let mut entries = self.entries.write().await;
+        let mut drained = Vec::with_capacity(entries.len());
+        while let Some((key, (value, _))) = entries.pop_lru() {
+            drained.push((key, value));
+        }
+        drained
+    }
+
+    /// Returns the keys of fresh entries that will expire within `window`
+    pub async fn expiring_within(&self, window: Duration) -> Vec<K>
+    where
+        K: Clone,
+    {
+        let now = self.clock.now();
+        let entries = self.entries.read().await;
+        entries
+            .iter()
+            .filter(|(_, (_, cached_at))| {
+                let age = now.saturating_duration_since(*cached_at);
+                age < self.ttl && age + window >= self.ttl
+            })
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// Returns the hit/miss counters and current number of entries
+    pub async fn stats(&self) -> CacheStats {
+        let entries = self.entries.read().await;
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            size: entries.len(),
+        }
+    }
+}
+
+impl<T: UserService> UserManager<T> {
+    /// Creates a new UserManager using the default cache TTL and capacity
+    pub fn new(service: T) -> Self {
+        Self::with_ttl(service, DEFAULT_CACHE_TTL)
+    }
+
+    /// Creates a new UserManager whose cache entries expire after `ttl`
+    pub fn with_ttl(service: T, ttl: Duration) -> Self {
+        Self {
+            service,
+            cache: TtlCache::with_capacity(ttl, DEFAULT_CACHE_CAPACITY),
+            flush_hook: None,
+        }
+    }
+
+    /// Creates a new UserManager whose cache entries expire after `ttl`, as measured by `clock`
+    pub fn with_clock(service: T, ttl: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            service,
+            cache: TtlCache::with_clock(ttl, DEFAULT_CACHE_CAPACITY, clock),
+            flush_hook: None,
+        }
+    }
+
+    /// Creates a new UserManager caching at most `capacity` users
+    pub fn with_capacity(service: T, capacity: usize) -> Self {
+        Self {
+            service,
+            cache: TtlCache::with_capacity(DEFAULT_CACHE_TTL, capacity),
+            flush_hook: None,
+        }
+    }
+
+    /// Registers a callback that receives the cached users whenever `flush` runs
+    pub fn on_flush<F, Fut>(&mut self, hook: F)
+    where
+        F: Fn(Vec<User>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), UserError>> + Send + 'static,
+    {
+        self.flush_hook = Some(Box::new(move |users| Box::pin(hook(users))));
+    }
+
+    /// Gets a user with caching, refreshing entries older than the TTL
+    #[cfg_attr(feature = "tracing", tracing::instrument(skip(self), fields(cache_hit = tracing::field::Empty)))]
+    pub async fn get_user_cached(&self, id: u32) -> Result<Option<User>, UserError> {
+        // Check cache first
+        let cached = self.cache.get(&id).await;
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("cache_hit", cached.is_some());
+        if let Some(user) = cached {
+            return Ok(Some(user));
+        }
+
+        // Fetch from service, replacing or evicting any expired entry
+        match self.service.get_user(id).await? {
+            Some(user) => {
+                self.cache.insert(id, user.clone()).await;
+                Ok(Some(user))
+            }
+            None => {
+                self.cache.remove(&id).await;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Creates a user through the service and caches the stored copy
+    pub async fn create_user(&self, user: User) -> Result<User, UserError> {
+        let created = self.service.create_user(user).await?;
+        self.cache.insert(created.id, created.clone()).await;
+        Ok(created)
+    }
+
+    /// Updates a user through the service, writing the result through to the cache.
+    ///
+    /// A failed update evicts the entry, since a conflict means the cached copy is stale.
+    pub async fn update_user(&self, user: User) -> Result<User, UserError> {
+        let id = user.id;
+        match self.service.update_user(user).await {
+            Ok(updated) => {
+                self.cache.insert(id, updated.clone()).await;
+                Ok(updated)
+            }
+            Err(e) => {
+                self.cache.remove(&id).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Invalidates the cache for a user
+    pub async fn invalidate_cache(&self, id: u32) {
+        self.cache.remove(&id).await;
+    }
+
+    /// Clears the entire cache
+    pub async fn clear_cache(&self) {
+        self.cache.clear().await;
+    }
+
+    /// Drains the cache, passing the users in id order to the `on_flush` hook if one is set.
+    ///
+    /// Async Drop isn't available, so call this explicitly before the manager goes away.
+    pub async fn flush(&self) -> Result<(), UserError> {
+        let mut users: Vec<User> = self.cache.drain().await.into_iter().map(|(_, user)| user).collect();
+        users.sort_by_key(|user| user.id);
+
+        match &self.flush_hook {
+            Some(hook) => hook(users).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Returns the cache hit/miss counters and current number of entries
+    pub async fn cache_stats(&self) -> CacheStats {
+        self.cache.stats().await
+    }
+
+    /// Re-fetches cached users every `interval` when they are within two intervals of expiring.
+    ///
+    /// The warmer only holds a weak reference, so it also stops once the manager is dropped.
+    pub fn spawn_warmer(self: &Arc<Self>, interval: Duration) -> CacheWarmer
+    where
+        T: Send + Sync + 'static,
+    {
+        let manager = Arc::downgrade(self);
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                manager.warm(interval * 2).await;
+            }
+        });
+
+        CacheWarmer { task }
+    }
+
+    /// Refreshes every cached user expiring within `window` from the service
+    async fn warm(&self, window: Duration) {
+        for id in self.cache.expiring_within(window).await {
+            match self.service.get_user(id).await {
+                Ok(Some(user)) => self.cache.insert(id, user).await,
+                Ok(None) => self.cache.remove(&id).await,
+                Err(e) => log::warn!("Failed to warm cached user {}: {}", id, e),
+            }
+        }
+    }
+}
+
+impl Drop for CacheWarmer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl<R, K, V> CachingRepository<R, K, V>
+where
+    R: Repository<K, V>,
+    K: Eq + Hash + Clone + Send + Sync,
+    V: Clone + Send + Sync,
+{
+    /// Wraps `inner` with a cache whose entries expire after `ttl`
+    pub fn new(inner: R, ttl: Duration) -> Self {
+        Self {
+            inner,
+            cache: TtlCache::new(ttl),
+        }
+    }
+
+    /// Returns the wrapped repository
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the cache hit/miss counters and current number of entries
+    pub async fn cache_stats(&self) -> CacheStats {
+        self.cache.stats().await
+    }
+}
+
+#[async_trait]
+impl<R, K, V> Repository<K, V> for CachingRepository<R, K, V>
+where
+    R: Repository<K, V>,
+    K: Eq + Hash + Clone + Send + Sync,
+    V: Clone + Send + Sync,
+{
+    async fn get(&self, key: &K) -> Result<Option<V>, UserError> {
+        if let Some(value) = self.cache.get(key).await {
+            return Ok(Some(value));
+        }
+
+        match self.inner.get(key).await? {
+            Some(value) => {
+                self.cache.insert(key.clone(), value.clone()).await;
+                Ok(Some(value))
+            }
+            None => {
+                self.cache.remove(key).await;
+                Ok(None)
+            }
+        }
+    }
+
+    async fn insert(&self, key: K, value: V) -> Result<(), UserError> {
+        self.inner.insert(key.clone(), value.clone()).await?;
+        self.cache.insert(key, value).await;
+        Ok(())
+    }
+
+    async fn remove(&self, key: &K) -> Result<Option<V>, UserError> {
+        let removed = self.inner.remove(key).await?;
+        self.cache.remove(key).await;
+        Ok(removed)
+    }
+
+    async fn list(&self) -> Result<Vec<V>, UserError> {
+        self.inner.list().await
+    }
+}
+
+impl<S, I: Interceptor> Layered<S, I> {
+    /// Wraps `inner` so every call passes through `interceptor`
+    pub fn new(inner: S, interceptor: I) -> Self {
+        Self { inner, interceptor }
+    }
+
+    /// Returns the wrapped service
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Runs one delegated call between the interceptor hooks
+    async fn intercept<T>(
+        &self,
+        operation: &'static str,
+        call: impl Future<Output = Result<T, UserError>>,
+    ) -> Result<T, UserError> {
+        self.interceptor.before(operation);
+        let started = Instant::now();
+        let result = call.await;
+        self.interceptor.after(operation, started.elapsed(), result.as_ref().err());
+        result
+    }
+}
+
+#[async_trait]
+impl<S: UserService + Sync, I: Interceptor> UserService for Layered<S, I> {
+    async fn get_user(&self, id: u32) -> Result<Option<User>, UserError> {
+        self.intercept("get_user", self.inner.get_user(id)).await
+    }
+
+    async fn get_user_by_uuid(&self, uuid: Uuid) -> Result<Option<User>, UserError> {
+        self.intercept("get_user_by_uuid", self.inner.get_user_by_uuid(uuid)).await
+    }
+
+    async fn get_users(&self, ids: &[u32]) -> Result<Vec<Option<User>>, UserError> {
+        self.intercept("get_users", self.inner.get_users(ids)).await
+    }
+
+    async fn create_user(&self, user: User) -> Result<User, UserError> {
+        self.intercept("create_user", self.inner.create_user(user)).await
+    }
+
+    async fn register_user(&self, new_user: NewUser, hasher: &dyn PasswordHasher) -> Result<User, UserError> {
+        self.intercept("register_user", self.inner.register_user(new_user, hasher)).await
+    }
+
+    async fn get_or_create_by_email(
+        &self,
+        new_user: NewUser,
+        hasher: &dyn PasswordHasher,
+    ) -> Result<(User, bool), UserError> {
+        self.intercept("get_or_create_by_email", self.inner.get_or_create_by_email(new_user, hasher))
+            .await
+    }
+
+    async fn update_user(&self, user: User) -> Result<User, UserError> {
+        self.intercept("update_user", self.inner.update_user(user)).await
+    }
+
+    async fn delete_user(&self, id: u32) -> Result<(), UserError> {
+        self.intercept("delete_user", self.inner.delete_user(id)).await
+    }
+
+    async fn list_users(&self) -> Result<Vec<User>, UserError> {
+        self.intercept("list_users", self.inner.list_users()).await
+    }
+
+    async fn list_users_paged(&self, offset: usize, limit: usize) -> Result<Page<User>, UserError> {
+        self.intercept("list_users_paged", self.inner.list_users_paged(offset, limit)).await
+    }
+
+    async fn list_users_after(&self, after_id: Option<u32>, limit: usize) -> Result<Vec<User>, UserError> {
+        self.intercept("list_users_after", self.inner.list_users_after(after_id, limit)).await
+    }
+
+    async fn find_by_email(&self, email: &str) -> Result<Option<User>, UserError> {
+        self.intercept("find_by_email", self.inner.find_by_email(email)).await
+    }
+
+    async fn create_users(&self, users: Vec<User>) -> Result<BatchResult, UserError> {
+        self.intercept("create_users", self.inner.create_users(users)).await
+    }
+
+    async fn soft_delete_user(&self, id: u32) -> Result<(), UserError> {
+        self.intercept("soft_delete_user", self.inner.soft_delete_user(id)).await
+    }
+
+    async fn list_users_including_deleted(&self) -> Result<Vec<User>, UserError> {
+        self.intercept("list_users_including_deleted", self.inner.list_users_including_deleted()).await
+    }
+
+    async fn search_users(&self, query: UserQuery) -> Result<Vec<User>, UserError> {
+        self.intercept("search_users", self.inner.search_users(query)).await
+    }
+
+    async fn count_users(&self) -> Result<usize, UserError> {
+        self.intercept("count_users", self.inner.count_users()).await
+    }
+
+    async fn count_all(&self) -> Result<usize, UserError> {
+        self.intercept("count_all", self.inner.count_all()).await
+    }
+
+    async fn change_email(&self, id: u32, new_email: String) -> Result<User, UserError> {
+        self.intercept("change_email", self.inner.change_email(id, new_email)).await
+    }
+
+    async fn health_check(&self) -> Result<(), UserError> {
+        self.intercept("health_check", self.inner.health_check()).await
+    }
+
+    async fn list_users_by_status(&self, status: Status) -> Result<Vec<User>, UserError> {
+        self.intercept("list_users_by_status", self.inner.list_users_by_status(status)).await
+    }
+
+    async fn patch_user(&self, id: u32, patch: UserPatch) -> Result<User, UserError> {
+        self.intercept("patch_user", self.inner.patch_user(id, patch)).await
+    }
+
+    async fn set_user_password(
+        &self,
+        id: u32,
+        new_password: &str,
+        hasher: &dyn PasswordHasher,
+    ) -> Result<(), UserError> {
+        self.intercept("set_user_password", self.inner.set_user_password(id, new_password, hasher))
+            .await
+    }
+
+    async fn mark_email_verified(&self, id: u32) -> Result<User, UserError> {
+        self.intercept("mark_email_verified", self.inner.mark_email_verified(id)).await
+    }
+
+    async fn set_active(&self, id: u32, active: bool) -> Result<User, UserError> {
+        self.intercept("set_active", self.inner.set_active(id, active)).await
+    }
+}
+
+impl Interceptor for LoggingLayer {
+    fn before(&self, operation: &'static str) {
+        log::debug!(target: "user_service", "-> {}", operation);
+    }
+
+    fn after(&self, operation: &'static str, _elapsed: Duration, error: Option<&UserError>) {
+        match error {
+            None => log::debug!(target: "user_service", "<- {} ok", operation),
+            Some(e) => log::warn!(target: "user_service", "<- {} failed: {}", operation, e),
+        }
+    }
+}
+
+impl<S: UserService + Sync> ServiceLayer<S> for LoggingLayer {
+    type Service = Layered<S, LoggingLayer>;
+
+    fn wrap(&self, inner: S) -> Self::Service {
+        Layered::new(inner, *self)
+    }
+}
+
+impl Interceptor for TimingLayer {
+    fn after(&self, operation: &'static str, elapsed: Duration, _error: Option<&UserError>) {
+        log::debug!(target: "user_service", "{} took {:?}", operation, elapsed);
+    }
+}
+
+impl<S: UserService + Sync> ServiceLayer<S> for TimingLayer {
+    type Service = Layered<S, TimingLayer>;
+
+    fn wrap(&self, inner: S) -> Self::Service {
+        Layered::new(inner, *self)
+    }
+}
+
+/// Authentication module
+pub mod auth {
+    use super::*;
+    use jsonwebtoken::errors::ErrorKind;
+    use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+    use serde::de::DeserializeOwned;
+
+    /// Default lifetime of access tokens
+    pub const ACCESS_TOKEN_TTL: Duration = Duration::from_secs(15 * 60);
+    /// Default lifetime of refresh tokens
+    pub const REFRESH_TOKEN_TTL: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+    /// Longest token lifetime `Config::validate` accepts
+    pub const MAX_TOKEN_TTL: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+    /// Minimum number of characters in a password
+    pub const MIN_PASSWORD_LENGTH: usize = 8;
+    /// Lifetime of email verification tokens
+    pub const VERIFICATION_TOKEN_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+    /// `purpose` claim carried by email verification tokens
+    const EMAIL_VERIFICATION_PURPOSE: &str = "email_verification";
+    
+    /// Roles used for authorization checks
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+    #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+    #[serde(rename_all = "snake_case")]
+    pub enum Role {
+        Admin,
+        User,
+        Guest,
+    }
+
+    /// JWT token claims, rejecting tokens issued for other purposes
+    #[derive(Debug, Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct Claims {
+        pub sub: u32,
+        pub exp: usize,
+        pub iat: usize,
+        #[serde(default)]
+        pub roles: Vec<Role>,
+    }
+
+    impl Claims {
+        /// Builds claims for `user_id` issued now and expiring after `ttl`
+        pub fn new(user_id: u32, roles: &[Role], ttl: Duration) -> Self {
+            Self::new_at(user_id, roles, ttl, &SystemClock)
+        }
+
+        /// Builds claims for `user_id` issued at `clock`'s current time and expiring after `ttl`
+        pub fn new_at(user_id: u32, roles: &[Role], ttl: Duration, clock: &dyn Clock) -> Self {
+            let iat = now_secs(clock);
+            Self {
+                sub: user_id,
+                exp: iat.saturating_add(ttl.as_secs() as usize),
+                iat,
+                roles: roles.to_vec(),
+            }
+        }
+
+        /// Builds claims for `user` carrying its roles, expiring after `ttl`
+        pub fn for_user(user: &User, ttl: Duration) -> Self {
+            Self::new(user.id, &user.roles, ttl)
+        }
+    }
+
+    /// JWT refresh token claims, identified by a single-use `jti`
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct RefreshClaims {
+        pub sub: u32,
+        pub exp: usize,
+        pub iat: usize,
+        pub jti: String,
+        #[serde(default)]
+        pub roles: Vec<Role>,
+    }
+
+    /// JWT email verification claims, scoped by `purpose` so they can't be used as access tokens
+    #[derive(Debug, Serialize, Deserialize)]
+    pub struct VerificationClaims {
+        pub sub: u32,
+        pub exp: usize,
+        pub iat: usize,
+        pub purpose: String,
+    }
+
+    /// Error types for token operations
+    #[derive(Debug, thiserror::Error)]
+    pub enum TokenError {
+        #[error("Token has expired")]
+        Expired,
+        #[error("Token signature is invalid")]
+        InvalidSignature,
+        #[error("Token is malformed")]
+        Malformed,
+        #[error("Token encoding failed: {0}")]
+        Encoding(String),
+        #[error("Refresh token has already been used or revoked")]
+        Revoked,
+    }
+    
+    /// Signing and verification keys for one JWT algorithm
+    #[derive(Clone)]
+    pub struct TokenKeys {
+        algorithm: Algorithm,
+        encoding: EncodingKey,
+        decoding: DecodingKey,
+    }
+
+    /// Limits for RateLimiter: `max_attempts` refill evenly over each `window`
+    #[derive(Debug, Clone, Copy)]
+    pub struct RateLimitConfig {
+        pub max_attempts: u32,
+        pub window: Duration,
+    }
+
+    impl Default for RateLimitConfig {
+        fn default() -> Self {
+            Self {
+                max_attempts: 5,
+                window: Duration::from_secs(60),
+            }
+        }
+    }
+
+    /// Token bucket for a single key
+    #[derive(Debug, Clone, Copy)]
+    pub struct BucketState {
+        tokens: f64,
+        last_refill: Instant,
+    }
+
+    /// RateLimiter throttles attempts per key using a token bucket
+    pub struct RateLimiter {
+        config: RateLimitConfig,
+        buckets: RwLock<HashMap<String, BucketState>>,
+        swept_at: Mutex<Instant>,
+        clock: Arc<dyn Clock>,
+    }
+
+    /// Limits for AccountLockout: `max_failures` failed logins in a row lock the account for `duration`
+    #[derive(Debug, Clone, Copy)]
+    pub struct LockoutConfig {
+        pub max_failures: u32,
+        pub duration: Duration,
+    }
+
+    impl Default for LockoutConfig {
+        fn default() -> Self {
+            Self {
+                max_failures: 5,
+                duration: Duration::from_secs(15 * 60),
+            }
+        }
+    }
+
+    /// Failed login count and lock expiry for a single account
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct LockState {
+        failures: u32,
+        locked_until: Option<Instant>,
+    }
+
+    /// RefreshStore tracks unused refresh token ids until they expire, so each can be redeemed once
+    pub struct RefreshStore {
+        tokens: Mutex<HashMap<String, usize>>,
+        clock: Arc<dyn Clock>,
+    }
+
+    /// AccountLockout locks an account after repeated failed logins, whatever email casing or client they came from
+    pub struct AccountLockout {
+        config: LockoutConfig,
+        states: RwLock<HashMap<u32, LockState>>,
+        clock: Arc<dyn Clock>,
+    }
+
+    impl RateLimiter {
+        /// Creates a limiter using the system clock
+        pub fn new(config: RateLimitConfig) -> Self {
+            Self::with_clock(config, Arc::new(SystemClock))
+        }
+
+        /// Creates a limiter that reads the current time from `clock`
+        pub fn with_clock(config: RateLimitConfig, clock: Arc<dyn Clock>) -> Self {
+            Self {
+                config,
+                buckets: RwLock::new(HashMap::new()),
+                swept_at: Mutex::new(clock.now()),
+                clock,
+            }
+        }
+
+        /// Returns how many keys currently have a bucket
+        pub async fn tracked_keys(&self) -> usize {
+            self.buckets.read().await.len()
+        }
+
+        /// Consumes one attempt for `key`, failing with `RateLimited` when none are left.
+        ///
+        /// At most once per window, buckets idle for a whole window are dropped; they would
+        /// have refilled completely, so a fresh bucket behaves the same.
+        pub async fn check(&self, key: &str) -> Result<(), UserError> {
+            let now = self.clock.now();
+            let capacity = f64::from(self.config.max_attempts);
+            let mut buckets = self.buckets.write().await;
+            {
+                let mut swept_at = self.swept_at.lock().unwrap_or_else(|e| e.into_inner());
+                if now.saturating_duration_since(*swept_at) >= self.config.window {
+                    buckets.retain(|_, bucket| now.saturating_duration_since(bucket.last_refill) < self.config.window);
+                    *swept_at = now;
+                }
+            }
+            let bucket = buckets.entry(key.to_string()).or_insert(BucketState {
+                tokens: capacity,
+                last_refill: now,
+            });
+
+            let elapsed = now.saturating_duration_since(bucket.last_refill);
+            let refill = elapsed.as_secs_f64() / self.config.window.as_secs_f64().max(f64::EPSILON) * capacity;
+            bucket.tokens = (bucket.tokens + refill).min(capacity);
+            bucket.last_refill = now;
+
+            if bucket.tokens < 1.0 {
+                return Err(UserError::RateLimited);
+            }
+            bucket.tokens -= 1.0;
+            Ok(())
+        }
+    }
+
+    impl RefreshStore {
+        /// Creates an empty store using the system clock
+        pub fn new() -> Self {
+            Self::with_clock(Arc::new(SystemClock))
+        }
+
+        /// Creates an empty store that reads the current time from `clock`
+        pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+            Self {
+                tokens: Mutex::new(HashMap::new()),
+                clock,
+            }
+        }
+
+        /// Returns how many issued refresh tokens are still unused and unexpired
+        pub fn len(&self) -> usize {
+            self.live_tokens().len()
+        }
+
+        /// Returns true when no redeemable refresh tokens remain
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        /// Records `jti` as redeemable until `exp`
+        fn insert(&self, jti: String, exp: usize) {
+            self.live_tokens().insert(jti, exp);
+        }
+
+        /// Consumes `jti`, returning false if it was never issued, already used or has expired
+        fn redeem(&self, jti: &str) -> bool {
+            self.live_tokens().remove(jti).is_some()
+        }
+
+        /// Locks the token map after evicting every expired entry
+        fn live_tokens(&self) -> std::sync::MutexGuard<'_, HashMap<String, usize>> {
+            let now = now_secs(&*self.clock);
+            let mut tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+            tokens.retain(|_, exp| *exp >= now);
+            tokens
+        }
+    }
+
+    impl Default for RefreshStore {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl AccountLockout {
+        /// Creates a lockout using the system clock
+        pub fn new(config: LockoutConfig) -> Self {
+            Self::with_clock(config, Arc::new(SystemClock))
+        }
+
+        /// Creates a lockout that reads the current time from `clock`
+        pub fn with_clock(config: LockoutConfig, clock: Arc<dyn Clock>) -> Self {
+            Self {
+                config,
+                states: RwLock::new(HashMap::new()),
+                clock,
+            }
+        }
+
+        /// Fails with `Locked` while `user_id` is locked, clearing the lock once it has expired
+        pub async fn check(&self, user_id: u32) -> Result<(), UserError> {
+            let now = self.clock.now();
+            let mut states = self.states.write().await;
+            match states.get(&user_id).and_then(|state| state.locked_until) {
+                Some(until) if now < until => Err(UserError::Locked),
+                Some(_) => {
+                    states.remove(&user_id);
+                    Ok(())
+                }
+                None => Ok(()),
+            }
+        }
+
+        /// Counts a failed login, locking the account once `max_failures` is reached
+        pub async fn record_failure(&self, user_id: u32) {
+            let now = self.clock.now();
+            let mut states = self.states.write().await;
+            let state = states.entry(user_id).or_default();
+            state.failures += 1;
+            if state.failures >= self.config.max_failures {
+                state.failures = 0;
+                state.locked_until = Some(now + self.config.duration);
+            }
+        }
+
+        /// Forgets earlier failures after a successful login
+        pub async fn record_success(&self, user_id: u32) {
+            self.states.write().await.remove(&user_id);
+        }
+    }
+
+    /// Rejects passwords that are too short or lack a letter or a digit
+    pub fn validate_password_strength(password: &str) -> Result<(), UserError> {
+        if password.chars().count() < MIN_PASSWORD_LENGTH {
+            return Err(UserError::InvalidInput(format!(
+                "Password must be at least {} characters",
+                MIN_PASSWORD_LENGTH
+            )));
+        }
+
+        if !password.chars().any(|c| c.is_ascii_digit()) {
+            return Err(UserError::InvalidInput("Password must contain a digit".to_string()));
+        }
+
+        if !password.chars().any(char::is_alphabetic) {
+            return Err(UserError::InvalidInput("Password must contain a letter".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Authenticates a user with email and password, throttled per email by `limiter`
+    pub async fn authenticate_user(
+        service: &dyn UserService,
+        hasher: &dyn PasswordHasher,
+        limiter: &RateLimiter,
+        email: &str,
+        password: &str,
+    ) -> Result<Option<User>, UserError> {
+        authenticate(service, hasher, limiter, None, email, password).await
+    }
+
+    /// Like `authenticate_user`, but refuses locked accounts with `Locked` even when the password is correct
+    pub async fn authenticate_user_with_lockout(
+        service: &dyn UserService,
+        hasher: &dyn PasswordHasher,
+        limiter: &RateLimiter,
+        lockout: &AccountLockout,
+        email: &str,
+        password: &str,
+    ) -> Result<Option<User>, UserError> {
+        authenticate(service, hasher, limiter, Some(lockout), email, password).await
+    }
+
+    /// Shared login flow; failures only count towards a lockout when the email belongs to a user
+    async fn authenticate(
+        service: &dyn UserService,
+        hasher: &dyn PasswordHasher,
+        limiter: &RateLimiter,
+        lockout: Option<&AccountLockout>,
+        email: &str,
+        password: &str,
+    ) -> Result<Option<User>, UserError> {
+        let allowed = limiter.check(&utils::normalize_email(email)).await;
+        #[cfg(feature = "metrics")]
+        if allowed.is_err() {
+            metrics::counter!("auth_attempts_total", "result" => "rate_limited").increment(1);
+        }
+        allowed?;
+
+        let found = service.find_by_email(email).await?;
+        if let (Some(user), Some(lockout)) = (&found, lockout) {
+            let unlocked = lockout.check(user.id).await;
+            #[cfg(feature = "metrics")]
+            if unlocked.is_err() {
+                metrics::counter!("auth_attempts_total", "result" => "locked").increment(1);
+            }
+            unlocked?;
+        }
+
+        let authenticated = match found {
+            Some(user) if user.verify_password(password, hasher)? => {
+                if !user.active {
+                    #[cfg(feature = "metrics")]
+                    metrics::counter!("auth_attempts_total", "result" => "inactive").increment(1);
+                    return Err(UserError::Authentication);
+                }
+                if let Some(lockout) = lockout {
+                    lockout.record_success(user.id).await;
+                }
+                Some(rehash_if_outdated(service, hasher, user, password).await)
+            }
+            Some(user) => {
+                if let Some(lockout) = lockout {
+                    lockout.record_failure(user.id).await;
+                }
+                None
+            }
+            None => {
+                hasher.verify_dummy(password);
+                None
+            }
+        };
+        #[cfg(feature = "metrics")]
+        metrics::counter!(
+            "auth_attempts_total",
+            "result" => if authenticated.is_some() { "success" } else { "failure" }
+        )
+        .increment(1);
+        Ok(authenticated)
+    }
+
+    /// Re-hashes a verified password when the stored hash uses outdated parameters.
+    ///
+    /// This is best effort: if the update fails the login still succeeds with the old hash.
+    async fn rehash_if_outdated(
+        service: &dyn UserService,
+        hasher: &dyn PasswordHasher,
+        user: User,
+        password: &str,
+    ) -> User {
+        if !hasher.needs_rehash(&user.password_hash) {
+            return user;
+        }
+
+        let mut rehashed = user.clone();
+        let result = match hasher.hash(password) {
+            Ok(hash) => {
+                rehashed.password_hash = hash;
+                service.update_user(rehashed).await
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(updated) => updated,
+            Err(e) => {
+                log::warn!("Failed to rehash password for user {}: {}", user.id, e);
+                user
+            }
+        }
+    }
+    
+    impl TokenKeys {
+        /// Uses HS256 with a shared secret, the default for `generate_token`
+        pub fn hs256(secret: &str) -> Self {
+            Self::hmac(Algorithm::HS256, secret.as_bytes())
+        }
+
+        /// Uses an HMAC algorithm (HS256, HS384 or HS512) with a shared secret
+        pub fn hmac(algorithm: Algorithm, secret: &[u8]) -> Self {
+            Self {
+                algorithm,
+                encoding: EncodingKey::from_secret(secret),
+                decoding: DecodingKey::from_secret(secret),
+            }
+        }
+
+        /// Uses an RSA algorithm (RS* or PS*) with a PEM private key for signing and PEM public key for verifying
+        pub fn rsa_pem(algorithm: Algorithm, private_pem: &[u8], public_pem: &[u8]) -> Result<Self, TokenError> {
+            let invalid_key = |e: jsonwebtoken::errors::Error| TokenError::Encoding(e.to_string());
+            Ok(Self {
+                algorithm,
+                encoding: EncodingKey::from_rsa_pem(private_pem).map_err(invalid_key)?,
+                decoding: DecodingKey::from_rsa_pem(public_pem).map_err(invalid_key)?,
+            })
+        }
+
+        /// Returns the algorithm tokens are signed and verified with
+        pub fn algorithm(&self) -> Algorithm {
+            self.algorithm
+        }
+    }
+
+    impl fmt::Debug for TokenKeys {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            f.debug_struct("TokenKeys")
+                .field("algorithm", &self.algorithm)
+                .finish_non_exhaustive()
+        }
+    }
+
+    /// Generates an HS256 JWT token for a user that expires after `ttl`
+    pub fn generate_token(
+        user_id: u32,
+        roles: &[Role],
+        secret: &str,
+        ttl: Duration,
+    ) -> Result<String, TokenError> {
+        generate_token_with(user_id, roles, &TokenKeys::hs256(secret), ttl)
+    }
+
+    /// Generates a JWT token for a user signed with `keys`, expiring after `ttl`
+    pub fn generate_token_with(
+        user_id: u32,
+        roles: &[Role],
+        keys: &TokenKeys,
+        ttl: Duration,
+    ) -> Result<String, TokenError> {
+        encode_claims(&Claims::new(user_id, roles, ttl), keys)
+    }
+
+    /// Generates an HS256 JWT token for a user, issued at `clock`'s current time
+    pub fn generate_token_at(
+        user_id: u32,
+        roles: &[Role],
+        secret: &str,
+        ttl: Duration,
+        clock: &dyn Clock,
+    ) -> Result<String, TokenError> {
+        encode_claims(&Claims::new_at(user_id, roles, ttl, clock), &TokenKeys::hs256(secret))
+    }
+    
+    /// Validates an HS256 JWT token and returns its claims
+    pub fn validate_token(token: &str, secret: &str) -> Result<Claims, TokenError> {
+        validate_token_with(token, &TokenKeys::hs256(secret))
+    }
+
+    /// Validates a JWT token against `keys`, rejecting tokens signed with any other algorithm
+    pub fn validate_token_with(token: &str, keys: &TokenKeys) -> Result<Claims, TokenError> {
+        decode_claims(token, keys)
+    }
+
+    /// Validates a JWT token, judging expiry by `clock` instead of the system time
+    pub fn validate_token_at(token: &str, secret: &str, clock: &dyn Clock) -> Result<Claims, TokenError> {
+        decode_claims_at(token, &TokenKeys::hs256(secret), clock)
+    }
+
+    /// Generates a single-use refresh token for a user, recorded in `store`, that expires after `REFRESH_TOKEN_TTL`
+    pub fn generate_refresh_token(
+        user_id: u32,
+        roles: &[Role],
+        secret: &str,
+        store: &RefreshStore,
+    ) -> Result<String, TokenError> {
+        generate_refresh_token_with_ttl(user_id, roles, secret, REFRESH_TOKEN_TTL, store)
+    }
+
+    /// Generates a single-use refresh token for a user, recorded in `store`, that expires after `ttl`
+    pub fn generate_refresh_token_with_ttl(
+        user_id: u32,
+        roles: &[Role],
+        secret: &str,
+        ttl: Duration,
+        store: &RefreshStore,
+    ) -> Result<String, TokenError> {
+        let iat = now_secs(&*store.clock);
+        let claims = RefreshClaims {
+            sub: user_id,
+            exp: iat.saturating_add(ttl.as_secs() as usize),
+            iat,
+            jti: format!("{}-{}", iat, utils::generate_id()),
+            roles: roles.to_vec(),
+        };
+
+        let token = encode_claims(&claims, &TokenKeys::hs256(secret))?;
+        store.insert(claims.jti, claims.exp);
+        Ok(token)
+    }
+
+    /// Exchanges a refresh token issued into `store` for a new access token and a rotated refresh token.
+    ///
+    /// The presented refresh token is invalidated, so reusing it fails with `TokenError::Revoked`.
+    pub fn exchange_refresh_token(
+        refresh: &str,
+        secret: &str,
+        store: &RefreshStore,
+    ) -> Result<(String, String), TokenError> {
+        exchange(refresh, secret, ACCESS_TOKEN_TTL, REFRESH_TOKEN_TTL, store)
+    }
+
+    /// Like `exchange_refresh_token`, using the secret and token lifetimes from `config`
+    pub fn exchange_refresh_token_with_config(
+        refresh: &str,
+        config: &Config,
+        store: &RefreshStore,
+    ) -> Result<(String, String), TokenError> {
+        exchange(refresh, &config.jwt_secret, config.access_token_ttl(), config.refresh_token_ttl(), store)
+    }
+
+    /// Issues an access and refresh token pair using the secret and token lifetimes from `config`
+    pub fn issue_tokens(
+        user_id: u32,
+        roles: &[Role],
+        config: &Config,
+        store: &RefreshStore,
+    ) -> Result<(String, String), TokenError> {
+        let access = generate_token_at(user_id, roles, &config.jwt_secret, config.access_token_ttl(), &*store.clock)?;
+        let refresh = generate_refresh_token_with_ttl(user_id, roles, &config.jwt_secret, config.refresh_token_ttl(), store)?;
+        Ok((access, refresh))
+    }
+
+    /// Redeems `refresh` once from `store` and issues a new pair with the given lifetimes
+    fn exchange(
+        refresh: &str,
+        secret: &str,
+        access_ttl: Duration,
+        refresh_ttl: Duration,
+        store: &RefreshStore,
+    ) -> Result<(String, String), TokenError> {
+        let claims: RefreshClaims = decode_claims_at(refresh, &TokenKeys::hs256(secret), &*store.clock)?;
+        if !store.redeem(&claims.jti) {
+            return Err(TokenError::Revoked);
+        }
+
+        let access = generate_token_at(claims.sub, &claims.roles, secret, access_ttl, &*store.clock)?;
+        let rotated = generate_refresh_token_with_ttl(claims.sub, &claims.roles, secret, refresh_ttl, store)?;
+        Ok((access, rotated))
+    }
+
+    /// Signs claims as a JWT with `keys`
+    fn encode_claims<C: Serialize>(claims: &C, keys: &TokenKeys) -> Result<String, TokenError> {
+        jsonwebtoken::encode(&Header::new(keys.algorithm), claims, &keys.encoding)
+            .map_err(|e| TokenError::Encoding(e.to_string()))
+    }
+
+    /// Verifies a JWT with `keys` and decodes its claims
+    fn decode_claims<C: DeserializeOwned>(token: &str, keys: &TokenKeys) -> Result<C, TokenError> {
+        decode_claims_at(token, keys, &SystemClock)
+    }
+
+    /// Verifies a JWT with `keys` and decodes its claims, checking `exp` against `clock` with the default leeway
+    fn decode_claims_at<C: DeserializeOwned>(token: &str, keys: &TokenKeys, clock: &dyn Clock) -> Result<C, TokenError> {
+        let mut validation = Validation::new(keys.algorithm);
+        validation.validate_exp = false;
+
+        let claims = jsonwebtoken::decode::<serde_json::Value>(token, &keys.decoding, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| match e.kind() {
+                ErrorKind::InvalidSignature => TokenError::InvalidSignature,
+                _ => TokenError::Malformed,
+            })?;
+
+        let exp = claims.get("exp").and_then(serde_json::Value::as_u64).ok_or(TokenError::Malformed)?;
+        if exp.saturating_add(validation.leeway) < now_secs(clock) as u64 {
+            return Err(TokenError::Expired);
+        }
+        serde_json::from_value(claims).map_err(|_| TokenError::Malformed)
+    }
+
+    /// Generates a short-lived token proving control of the user's email address
+    pub fn generate_verification_token(user_id: u32, secret: &str) -> Result<String, TokenError> {
+        generate_verification_token_at(user_id, secret, &SystemClock)
+    }
+
+    /// Like `generate_verification_token`, issued at `clock`'s current time
+    pub fn generate_verification_token_at(user_id: u32, secret: &str, clock: &dyn Clock) -> Result<String, TokenError> {
+        let iat = now_secs(clock);
+        let claims = VerificationClaims {
+            sub: user_id,
+            exp: iat.saturating_add(VERIFICATION_TOKEN_TTL.as_secs() as usize),
+            iat,
+            purpose: EMAIL_VERIFICATION_PURPOSE.to_string(),
+        };
+
+        encode_claims(&claims, &TokenKeys::hs256(secret))
+    }
+
+    /// Validates an email verification token and returns the user id it was issued for
+    pub fn verify_email_token(token: &str, secret: &str) -> Result<u32, UserError> {
+        let claims: VerificationClaims = decode_claims(token, &TokenKeys::hs256(secret)).map_err(|e| {
+            log::debug!("Rejected verification token: {}", e);
+            UserError::Authentication
+        })?;
+        if claims.purpose != EMAIL_VERIFICATION_PURPOSE {
+            return Err(UserError::Authentication);
+        }
+        Ok(claims.sub)
+    }
+
+    /// Resolves a bearer token to its user, failing with `Authentication` if the token is bad or the user is gone or inactive
+    pub async fn authenticate_request(service: &dyn UserService, token: &str, secret: &str) -> Result<User, UserError> {
+        let claims = validate_token(token, secret).map_err(|e| {
+            log::debug!("Rejected request token: {}", e);
+            UserError::Authentication
+        })?;
+        service
+            .get_user(claims.sub)
+            .await?
+            .filter(|user| user.active)
+            .ok_or(UserError::Authentication)
+    }
+
+    /// Ensures the token claims grant `role`
+    pub fn require_role(claims: &Claims, role: Role) -> Result<(), UserError> {
+        if claims.roles.contains(&role) {
+            Ok(())
+        } else {
+            Err(UserError::Authentication)
+        }
+    }
+
+    /// Ensures `user` is an admin
+    pub fn require_admin(user: &User) -> Result<(), UserError> {
+        if user.is_admin() {
+            Ok(())
+        } else {
+            Err(UserError::Authentication)
+        }
+    }
+
+    /// Returns `clock`'s current time as seconds since the Unix epoch
+    fn now_secs(clock: &dyn Clock) -> usize {
+        clock
+            .system_now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as usize
+    }
+}
+
+/// Utility functions
+pub mod utils {
+    use super::*;
+    use once_cell::sync::Lazy;
+    use regex::Regex;
+    use subtle::ConstantTimeEq;
+    use unicode_normalization::UnicodeNormalization;
+    
+    /// Generates a unique ID
+    pub fn generate_id() -> u32 {
+        GLOBAL_COUNTER.fetch_add(1, Ordering::Relaxed) + 1
+    }
+    
+    /// Normalizes an email address for comparison by trimming and lowercasing
+    pub fn normalize_email(email: &str) -> String {
+        email.trim().to_lowercase()
+    }
+    
+    /// Folds a name for case-insensitive comparison by NFC-normalizing and lowercasing it
+    pub fn fold_name(name: &str) -> String {
+        name.nfc().collect::<String>().to_lowercase()
+    }
+    
+    /// Matches a local part, a single `@`, and a dotted domain with no whitespace
+    static EMAIL_REGEX: Lazy<Regex> = Lazy::new(|| {
+        Regex::new(r"^[^\s@]+@[A-Za-z0-9](?:[A-Za-z0-9-]*[A-Za-z0-9])?(?:\.[A-Za-z0-9](?:[A-Za-z0-9-]*[A-Za-z0-9])?)+$")
+            .expect("email regex is valid")
+    });
+
+    /// Validates an email address
+    pub fn is_valid_email(email: &str) -> bool {
+        EMAIL_REGEX.is_match(email)
+    }
+    
+    /// Compares two strings without short-circuiting on the first differing byte
+    pub fn constant_time_eq(a: &str, b: &str) -> bool {
+        a.as_bytes().ct_eq(b.as_bytes()).into()
+    }
+    
+    /// Runs `f` up to `attempts` times, retrying transient errors with exponential backoff
+    pub async fn retry_on_transient<F, Fut, T>(attempts: u32, backoff: Duration, mut f: F) -> Result<T, UserError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, UserError>>,
+    {
+        let mut delay = backoff;
+        let mut attempt = 1;
+        loop {
+            match f().await {
+                Err(e) if e.is_transient() && attempt < attempts => {
+                    log::warn!("Retrying after transient error (attempt {}/{}): {}", attempt, attempts, e);
+                    tokio::time::sleep(delay).await;
+                    delay = delay.saturating_mul(2);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+    
+    /// Formats a user for display
+    pub fn format_user(user: &User) -> String {
+        user.formatted_name(NameStyle::WithEmail)
+    }
+}
+
+/// Postgres-backed user storage
+#[cfg(feature = "postgres")]
+pub mod postgres {
+    use super::*;
+    use sqlx::postgres::PgPool;
+    use sqlx::types::Json;
+
+    /// Creates the users table if it does not exist yet, then adds columns introduced since
+    /// the table was first shipped. Every statement is idempotent and they run in order.
+    pub const USERS_TABLE_MIGRATIONS: &[&str] = &[
+        "CREATE TABLE IF NOT EXISTS users (
+            id BIGSERIAL PRIMARY KEY,
+            uuid UUID NOT NULL UNIQUE,
+            name TEXT NOT NULL,
+            email TEXT NOT NULL UNIQUE,
+            password_hash TEXT NOT NULL DEFAULT '',
+            version BIGINT NOT NULL DEFAULT 0,
+            deleted_at TIMESTAMPTZ,
+            roles JSONB NOT NULL DEFAULT '[]',
+            status TEXT NOT NULL DEFAULT 'pending',
+            email_verified BOOLEAN NOT NULL DEFAULT false,
+            active BOOLEAN NOT NULL DEFAULT true,
+            metadata JSONB NOT NULL DEFAULT '{}',
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        )",
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS uuid UUID",
+        "UPDATE users SET uuid = gen_random_uuid() WHERE uuid IS NULL",
+        "ALTER TABLE users ALTER COLUMN uuid SET NOT NULL",
+        "CREATE UNIQUE INDEX IF NOT EXISTS users_uuid_key ON users (uuid)",
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS status TEXT NOT NULL DEFAULT 'pending'",
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified BOOLEAN NOT NULL DEFAULT false",
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS active BOOLEAN NOT NULL DEFAULT true",
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS metadata JSONB NOT NULL DEFAULT '{}'",
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now()",
+        "ALTER TABLE users ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now()",
+    ];
+
+    const USER_COLUMNS: &str = "id, uuid, name, email, password_hash, version, \
+        EXTRACT(EPOCH FROM deleted_at)::float8 AS deleted_at, roles, status, email_verified, active, metadata, \
+        EXTRACT(EPOCH FROM created_at)::float8 AS created_at, \
+        EXTRACT(EPOCH FROM updated_at)::float8 AS updated_at";
+
+    /// PgUserService implements UserService on top of a Postgres pool, with optional read replica
+    pub struct PgUserService {
+        pool: PgPool,
+        replica: Option<PgPool>,
+    }
+
+    /// Row shape returned by user queries
+    #[derive(sqlx::FromRow)]
+    struct UserRow {
+        id: i64,
+        uuid: Uuid,
+        name: String,
+        email: String,
+        password_hash: String,
+        version: i64,
+        deleted_at: Option<f64>,
+        roles: Json<Vec<auth::Role>>,
+        status: String,
+        email_verified: bool,
+        active: bool,
+        metadata: Json<HashMap<String, serde_json::Value>>,
+        created_at: f64,
+        updated_at: f64,
+    }
+
+    impl From<UserRow> for User {
+        fn from(row: UserRow) -> Self {
+            User {
+                id: row.id as u32,
+                uuid: row.uuid,
+                name: row.name,
+                email: row.email,
+                password_hash: row.password_hash,
+                deleted_at: row.deleted_at.map(from_epoch_secs),
+                version: row.version as u64,
+                roles: row.roles.0,
+                status: row.status.parse().unwrap_or_default(),
+                email_verified: row.email_verified,
+                active: row.active,
+                metadata: row.metadata.0,
+                created_at: from_epoch_secs(row.created_at),
+                updated_at: from_epoch_secs(row.updated_at),
+            }
+        }
+    }
+
+    impl PgUserService {
+        /// Creates a new PgUserService using the given pool
+        pub fn new(pool: PgPool) -> Self {
+            Self { pool, replica: None }
+        }
+
+        /// Sends lag-tolerant reads to `replica` instead of the primary pool
+        pub fn with_read_replica(mut self, replica: PgPool) -> Self {
+            self.replica = Some(replica);
+            self
+        }
+
+        /// Connects to the database and returns a service for it
+        pub async fn connect(database_url: &str) -> Result<Self, UserError> {
+            let pool = PgPool::connect(database_url)
+                .await
+                .map_err(|_| UserError::Database(DatabaseError::ConnectionFailed))?;
+            Ok(Self::new(pool))
+        }
+
+        /// Connects to `config.database_url` and, if set, `config.read_replica_url`
+        pub async fn connect_with_config(config: &Config) -> Result<Self, UserError> {
+            let service = Self::connect(&config.database_url).await?;
+            match &config.read_replica_url {
+                Some(url) => {
+                    let replica = PgPool::connect(url)
+                        .await
+                        .map_err(|_| UserError::Database(DatabaseError::ConnectionFailed))?;
+                    Ok(service.with_read_replica(replica))
+                }
+                None => Ok(service),
+            }
+        }
+
+        /// Runs the schema migrations, bringing older tables up to date
+        pub async fn migrate(&self) -> Result<(), UserError> {
+            for statement in USERS_TABLE_MIGRATIONS {
+                sqlx::query(statement)
+                    .execute(&self.pool)
+                    .await
+                    .map_err(map_sqlx_error)?;
+            }
+            Ok(())
+        }
+
+        /// Fetches users matching a WHERE clause, ordered by id
+        async fn fetch_users(&self, filter: &str) -> Result<Vec<User>, UserError> {
+            let rows = sqlx::query_as::<_, UserRow>(&format!(
+                "SELECT {} FROM users {} ORDER BY id",
+                USER_COLUMNS, filter
+            ))
+            .fetch_all(self.reader())
+            .await
+            .map_err(map_sqlx_error)?;
+            Ok(rows.into_iter().map(User::from).collect())
+        }
+
+        /// Looks up a live user by id on the given pool
+        async fn get_user_on(&self, pool: &PgPool, id: u32) -> Result<Option<User>, UserError> {
+            let row = sqlx::query_as::<_, UserRow>(&format!(
+                "SELECT {} FROM users WHERE id = $1 AND deleted_at IS NULL",
+                USER_COLUMNS
+            ))
+            .bind(i64::from(id))
+            .fetch_optional(pool)
+            .await
+            .map_err(map_sqlx_error)?;
+            Ok(row.map(User::from))
+        }
+
+        /// Looks up a live user by email on the given pool
+        async fn find_by_email_on(&self, pool: &PgPool, email: &str) -> Result<Option<User>, UserError> {
+            let row = sqlx::query_as::<_, UserRow>(&format!(
+                "SELECT {} FROM users WHERE email = $1 AND deleted_at IS NULL",
+                USER_COLUMNS
+            ))
+            .bind(utils::normalize_email(email))
+            .fetch_optional(pool)
+            .await
+            .map_err(map_sqlx_error)?;
+            Ok(row.map(User::from))
+        }
+    }
+
+    #[async_trait]
+    impl UserService for PgUserService {
+        async fn get_user(&self, id: u32) -> Result<Option<User>, UserError> {
+            self.get_user_on(self.reader(), id).await
+        }
+
+        async fn get_users(&self, ids: &[u32]) -> Result<Vec<Option<User>>, UserError> {
+            let keys: Vec<i64> = ids.iter().map(|id| i64::from(*id)).collect();
+            let rows = sqlx::query_as::<_, UserRow>(&format!(
+                "SELECT {} FROM users WHERE id = ANY($1) AND deleted_at IS NULL",
+                USER_COLUMNS
+            ))
+            .bind(&keys)
+            .fetch_all(self.reader())
+            .await
+            .map_err(map_sqlx_error)?;
+
+            let found: HashMap<u32, User> = rows.into_iter().map(User::from).map(|user| (user.id, user)).collect();
+            Ok(ids.iter().map(|id| found.get(id).cloned()).collect())
+        }
+
+        async fn get_user_by_uuid(&self, uuid: Uuid) -> Result<Option<User>, UserError> {
+            let row = sqlx::query_as::<_, UserRow>(&format!(
+                "SELECT {} FROM users WHERE uuid = $1 AND deleted_at IS NULL",
+                USER_COLUMNS
+            ))
+            .bind(uuid)
+            .fetch_optional(self.reader())
+            .await
+            .map_err(map_sqlx_error)?;
+            Ok(row.map(User::from))
+        }
+
+        async fn create_user(&self, mut user: User) -> Result<User, UserError> {
+            user.email = user.email_normalized();
+            user.validate()?;
+
+            let row = sqlx::query_as::<_, UserRow>(&format!(
+                "INSERT INTO users (uuid, name, email, password_hash, roles, status, metadata) \
+                 VALUES ($1, $2, $3, $4, $5, $6, $7) RETURNING {}",
+                USER_COLUMNS
+            ))
+            .bind(Uuid::new_v4())
+            .bind(&user.name)
+            .bind(&user.email)
+            .bind(&user.password_hash)
+            .bind(Json(&user.roles))
+            .bind(user.status.as_str())
+            .bind(Json(&user.metadata))
+            .fetch_one(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+            #[cfg(feature = "metrics")]
+            metrics::counter!("users_created_total").increment(1);
+            Ok(row.into())
+        }
+
+        async fn update_user(&self, mut user: User) -> Result<User, UserError> {
+            user.email = user.email_normalized();
+            user.validate()?;
+
+            let row = sqlx::query_as::<_, UserRow>(&format!(
+                "UPDATE users SET name = $1, email = $2, password_hash = $3, roles = $4, status = $7, \
+                 metadata = $8, version = version + 1, updated_at = now() \
+                 WHERE id = $5 AND version = $6 AND deleted_at IS NULL RETURNING {}",
+                USER_COLUMNS
+            ))
+            .bind(&user.name)
+            .bind(&user.email)
+            .bind(&user.password_hash)
+            .bind(Json(&user.roles))
+            .bind(i64::from(user.id))
+            .bind(user.version as i64)
+            .bind(user.status.as_str())
+            .bind(Json(&user.metadata))
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+            match row {
+                Some(row) => Ok(row.into()),
+                None if self.get_user_on(self.writer(), user.id).await?.is_some() => Err(UserError::Conflict),
+                None => Err(UserError::NotFound),
+            }
+        }
+
+        async fn delete_user(&self, id: u32) -> Result<(), UserError> {
+            let result = sqlx::query("DELETE FROM users WHERE id = $1")
+                .bind(i64::from(id))
+                .execute(&self.pool)
+                .await
+                .map_err(map_sqlx_error)?;
+
+            if result.rows_affected() == 0 {
+                return Err(UserError::NotFound);
+            }
+            #[cfg(feature = "metrics")]
+            metrics::counter!("users_deleted_total").increment(1);
+            Ok(())
+        }
+
+        async fn list_users(&self) -> Result<Vec<User>, UserError> {
+            self.fetch_users("WHERE deleted_at IS NULL").await
+        }
+
+        async fn list_users_paged(&self, offset: usize, limit: usize) -> Result<Page<User>, UserError> {
+            let total = self.count_users().await?;
+
+            let rows = sqlx::query_as::<_, UserRow>(&format!(
+                "SELECT {} FROM users WHERE deleted_at IS NULL ORDER BY id OFFSET $1 LIMIT $2",
+                USER_COLUMNS
+            ))
+            .bind(offset as i64)
+            .bind(limit as i64)
+            .fetch_all(self.reader())
+            .await
+            .map_err(map_sqlx_error)?;
+
+            let items: Vec<User> = rows.into_iter().map(User::from).collect();
+            let has_more = offset.saturating_add(items.len()) < total;
+            Ok(Page { items, total, has_more })
+        }
+
+        async fn list_users_after(&self, after_id: Option<u32>, limit: usize) -> Result<Vec<User>, UserError> {
+            let rows = sqlx::query_as::<_, UserRow>(&format!(
+                "SELECT {} FROM users WHERE deleted_at IS NULL AND id > $1 ORDER BY id LIMIT $2",
+                USER_COLUMNS
+            ))
+            .bind(after_id.map_or(0, i64::from))
+            .bind(limit as i64)
+            .fetch_all(self.reader())
+            .await
+            .map_err(map_sqlx_error)?;
+            Ok(rows.into_iter().map(User::from).collect())
+        }
+
+        async fn find_by_email(&self, email: &str) -> Result<Option<User>, UserError> {
+            self.find_by_email_on(self.reader(), email).await
+        }
+
+        async fn create_users(&self, users: Vec<User>) -> Result<BatchResult, UserError> {
+            let mut result = BatchResult::default();
+            for (index, user) in users.into_iter().enumerate() {
+                match self.create_user(user).await {
+                    Ok(created) => result.created.push(created),
+                    Err(e @ UserError::InvalidInput(_)) => result.failed.push((index, e)),
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(result)
+        }
+
+        async fn soft_delete_user(&self, id: u32) -> Result<(), UserError> {
+            let result = sqlx::query("UPDATE users SET deleted_at = now() WHERE id = $1 AND deleted_at IS NULL")
+                .bind(i64::from(id))
+                .execute(&self.pool)
+                .await
+                .map_err(map_sqlx_error)?;
+
+            if result.rows_affected() == 0 {
+                return Err(UserError::NotFound);
+            }
+            #[cfg(feature = "metrics")]
+            metrics::counter!("users_deleted_total").increment(1);
+            Ok(())
+        }
+
+        async fn list_users_including_deleted(&self) -> Result<Vec<User>, UserError> {
+            self.fetch_users("").await
+        }
+
+        async fn search_users(&self, query: UserQuery) -> Result<Vec<User>, UserError> {
+            let rows = sqlx::query_as::<_, UserRow>(&format!(
+                "SELECT {} FROM users WHERE deleted_at IS NULL \
+                 AND ($1::text IS NULL OR strpos(lower(normalize(name, NFC)), $1) > 0) \
+                 AND ($2::text IS NULL OR split_part(email, '@', 2) = $2) \
+                 ORDER BY {} OFFSET $4 LIMIT $3",
+                USER_COLUMNS,
+                query.sort_by.sql(query.descending)
+            ))
+            .bind(query.name_contains.map(|needle| utils::fold_name(&needle)))
+            .bind(query.email_domain.map(|domain| utils::normalize_email(&domain)))
+            .bind(query.limit.map(|limit| limit as i64))
+            .bind(query.offset as i64)
+            .fetch_all(self.reader())
+            .await
+            .map_err(map_sqlx_error)?;
+            Ok(rows.into_iter().map(User::from).collect())
+        }
+
+        async fn count_users(&self) -> Result<usize, UserError> {
+            let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE deleted_at IS NULL")
+                .fetch_one(self.reader())
+                .await
+                .map_err(map_sqlx_error)?;
+            Ok(count as usize)
+        }
+
+        async fn count_all(&self) -> Result<usize, UserError> {
+            let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users")
+                .fetch_one(self.reader())
+                .await
+                .map_err(map_sqlx_error)?;
+            Ok(count as usize)
+        }
+
+        async fn register_user(&self, new_user: NewUser, hasher: &dyn PasswordHasher) -> Result<User, UserError> {
+            let user = new_user.into_user(hasher)?;
+            self.create_user(user).await
+        }
+
+        /// Inserts with `ON CONFLICT DO NOTHING` so the email UNIQUE constraint settles races
+        async fn get_or_create_by_email(
+            &self,
+            new_user: NewUser,
+            hasher: &dyn PasswordHasher,
+        ) -> Result<(User, bool), UserError> {
+            if let Some(existing) = self.find_by_email_on(self.writer(), &new_user.email).await? {
+                return Ok((existing, false));
+            }
+            let mut user = new_user.into_user(hasher)?;
+            user.email = user.email_normalized();
+
+            let row = sqlx::query_as::<_, UserRow>(&format!(
+                "INSERT INTO users (uuid, name, email, password_hash, roles, status) \
+                 VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (email) DO NOTHING RETURNING {}",
+                USER_COLUMNS
+            ))
+            .bind(Uuid::new_v4())
+            .bind(&user.name)
+            .bind(&user.email)
+            .bind(&user.password_hash)
+            .bind(Json(&user.roles))
+            .bind(user.status.as_str())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+            if let Some(row) = row {
+                #[cfg(feature = "metrics")]
+                metrics::counter!("users_created_total").increment(1);
+                return Ok((row.into(), true));
+            }
+            // The conflicting row may be soft-deleted, in which case the email stays reserved
+            match self.find_by_email_on(self.writer(), &user.email).await? {
+                Some(existing) => Ok((existing, false)),
+                None => Err(UserError::InvalidInput("email already registered".to_string())),
+            }
+        }
+
+        /// Relies on the email UNIQUE constraint, surfaced as `InvalidInput` by `map_sqlx_error`
+        async fn change_email(&self, id: u32, new_email: String) -> Result<User, UserError> {
+            let new_email = utils::normalize_email(&new_email);
+            if !utils::is_valid_email(&new_email) {
+                return Err(UserError::InvalidInput("Invalid email format".to_string()));
+            }
+
+            let row = sqlx::query_as::<_, UserRow>(&format!(
+                "UPDATE users SET email = $1, version = version + 1, updated_at = now() \
+                 WHERE id = $2 AND deleted_at IS NULL RETURNING {}",
+                USER_COLUMNS
+            ))
+            .bind(&new_email)
+            .bind(i64::from(id))
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+            row.map(User::from).ok_or(UserError::NotFound)
+        }
+
+        /// Pings the database, reporting any failure as `ConnectionFailed`
+        async fn health_check(&self) -> Result<(), UserError> {
+            sqlx::query("SELECT 1")
+                .execute(&self.pool)
+                .await
+                .map_err(|e| {
+                    log::warn!("Database health check failed: {}", e);
+                    UserError::Database(DatabaseError::ConnectionFailed)
+                })?;
+            Ok(())
+        }
+
+        async fn list_users_by_status(&self, status: Status) -> Result<Vec<User>, UserError> {
+            let rows = sqlx::query_as::<_, UserRow>(&format!(
+                "SELECT {} FROM users WHERE deleted_at IS NULL AND status = $1 ORDER BY id",
+                USER_COLUMNS
+            ))
+            .bind(status.as_str())
+            .fetch_all(self.reader())
+            .await
+            .map_err(map_sqlx_error)?;
+            Ok(rows.into_iter().map(User::from).collect())
+        }
+
+        /// Read-modify-write guarded by the version check in `update_user`
+        async fn patch_user(&self, id: u32, patch: UserPatch) -> Result<User, UserError> {
+            let mut user = self.get_user_on(self.writer(), id).await?.ok_or(UserError::NotFound)?;
+            patch.apply_to(&mut user);
+            self.update_user(user).await
+        }
+
+        async fn set_user_password(
+            &self,
+            id: u32,
+            new_password: &str,
+            hasher: &dyn PasswordHasher,
+        ) -> Result<(), UserError> {
+            auth::validate_password_strength(new_password)?;
+            let password_hash = hasher.hash(new_password)?;
+
+            let result = sqlx::query(
+                "UPDATE users SET password_hash = $1, version = version + 1, updated_at = now() \
+                 WHERE id = $2 AND deleted_at IS NULL",
+            )
+            .bind(&password_hash)
+            .bind(i64::from(id))
+            .execute(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+            if result.rows_affected() == 0 {
+                return Err(UserError::NotFound);
+            }
+            Ok(())
+        }
+
+        async fn mark_email_verified(&self, id: u32) -> Result<User, UserError> {
+            let row = sqlx::query_as::<_, UserRow>(&format!(
+                "UPDATE users SET email_verified = true, version = version + 1, updated_at = now() \
+                 WHERE id = $1 AND deleted_at IS NULL RETURNING {}",
+                USER_COLUMNS
+            ))
+            .bind(i64::from(id))
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+            row.map(User::from).ok_or(UserError::NotFound)
+        }
+
+        async fn set_active(&self, id: u32, active: bool) -> Result<User, UserError> {
+            let row = sqlx::query_as::<_, UserRow>(&format!(
+                "UPDATE users SET active = $1, version = version + 1, updated_at = now() \
+                 WHERE id = $2 AND deleted_at IS NULL RETURNING {}",
+                USER_COLUMNS
+            ))
+            .bind(active)
+            .bind(i64::from(id))
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(map_sqlx_error)?;
+
+            row.map(User::from).ok_or(UserError::NotFound)
+        }
+    }
+
+    /// Reads may lag writes on a replica; read-your-write paths query `writer()` directly
+    impl ReadRouter for PgUserService {
+        type Handle = PgPool;
+
+        fn writer(&self) -> &PgPool {
+            &self.pool
+        }
+
+        fn reader(&self) -> &PgPool {
+            self.replica.as_ref().unwrap_or(&self.pool)
+        }
+    }
+
+    /// Converts Postgres epoch seconds into a SystemTime
+    fn from_epoch_secs(secs: f64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs_f64(secs.max(0.0))
+    }
+
+    /// Maps sqlx errors, surfacing unique violations as invalid input
+    fn map_sqlx_error(e: sqlx::Error) -> UserError {
+        match &e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => {
+                UserError::InvalidInput("email already registered".to_string())
+            }
+            _ => UserError::Database(e.into()),
+        }
+    }
+
+    impl From<sqlx::Error> for DatabaseError {
+        /// Treats pool, I/O and TLS failures as connection problems and everything else as query failures
+        fn from(e: sqlx::Error) -> Self {
+            match e {
+                sqlx::Error::Io(_)
+                | sqlx::Error::Tls(_)
+                | sqlx::Error::PoolTimedOut
+                | sqlx::Error::PoolClosed
+                | sqlx::Error::WorkerCrashed => DatabaseError::ConnectionFailed,
+                _ => DatabaseError::QueryFailed(e.to_string()),
+            }
+        }
+    }
+}
+
+/// Configurable fake UserService for downstream tests
+#[cfg(feature = "test-util")]
+pub mod mock {
+    use super::*;
+
+    type ErrorFactory = Box<dyn Fn() -> UserError + Send + Sync>;
+
+    /// MockUserService keeps users in memory, counts calls per method and can be told to fail
+    #[derive(Default)]
+    pub struct MockUserService {
+        users: Mutex<HashMap<u32, User>>,
+        failures: Mutex<HashMap<&'static str, ErrorFactory>>,
+        calls: Mutex<HashMap<&'static str, usize>>,
+    }
+
+    impl MockUserService {
+        /// Creates an empty mock that succeeds on every call
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Creates a mock preloaded with `users`, keeping their ids
+        pub fn with_users(users: impl IntoIterator<Item = User>) -> Self {
+            let mock = Self::new();
+            mock.users.lock().unwrap().extend(users.into_iter().map(|user| (user.id, user)));
+            mock
+        }
+
+        /// Makes every call to `method` fail with the error built by `error`
+        pub fn fail_with(&self, method: &'static str, error: impl Fn() -> UserError + Send + Sync + 'static) {
+            self.failures.lock().unwrap().insert(method, Box::new(error));
+        }
+
+        /// Lets `method` succeed again after `fail_with`
+        pub fn clear_failure(&self, method: &str) {
+            self.failures.lock().unwrap().remove(method);
+        }
+
+        /// Returns how many times `method` has been called, including failed calls
+        pub fn calls(&self, method: &str) -> usize {
+            self.calls.lock().unwrap().get(method).copied().unwrap_or(0)
+        }
+
+        /// Counts the call and returns the configured failure, if any
+        fn record(&self, method: &'static str) -> Result<(), UserError> {
+            *self.calls.lock().unwrap().entry(method).or_default() += 1;
+            match self.failures.lock().unwrap().get(method) {
+                Some(error) => Err(error()),
+                None => Ok(()),
+            }
+        }
+
+        /// Returns the users matching `pred` in id order
+        fn collect(&self, pred: impl Fn(&User) -> bool) -> Vec<User> {
+            let mut users: Vec<User> = self.users.lock().unwrap().values().filter(|user| pred(user)).cloned().collect();
+            users.sort_by_key(|user| user.id);
+            users
+        }
+
+        /// Validates and stores a new user under the next free id
+        fn insert_new(&self, user: User) -> Result<User, UserError> {
+            insert_locked(&mut self.users.lock().unwrap(), user)
+        }
+
+        /// Runs `f` against the active user with `id`, failing with `NotFound` otherwise
+        fn modify<R>(
+            &self,
+            id: u32,
+            f: impl FnOnce(&HashMap<u32, User>, &mut User) -> Result<R, UserError>,
+        ) -> Result<R, UserError> {
+            let mut users = self.users.lock().unwrap();
+            let mut user = match users.get(&id) {
+                Some(user) if !user.is_deleted() => user.clone(),
+                _ => return Err(UserError::NotFound),
+            };
+            let result = f(&users, &mut user)?;
+            users.insert(id, user);
+            Ok(result)
+        }
+    }
+
+    /// Validates `user` and stores it in `users` under the next free id
+    fn insert_locked(users: &mut HashMap<u32, User>, mut user: User) -> Result<User, UserError> {
+        user.email = user.email_normalized();
+        user.validate()?;
+        if email_taken(users, &user.email, None) {
+            return Err(UserError::InvalidInput("email already registered".to_string()));
+        }
+
+        user.id = users.keys().max().map_or(1, |id| id + 1);
+        user.uuid = Uuid::new_v4();
+        user.version = 0;
+        user.created_at = SystemTime::now();
+        user.updated_at = user.created_at;
+        users.insert(user.id, user.clone());
+        Ok(user)
+    }
+
+    /// Checks whether a user other than `except` holds `email`; soft-deleted users keep theirs reserved
+    fn email_taken(users: &HashMap<u32, User>, email: &str, except: Option<u32>) -> bool {
+        users.values().any(|user| user.email == email && Some(user.id) != except)
+    }
+
+    #[async_trait]
+    impl UserService for MockUserService {
+        async fn get_user(&self, id: u32) -> Result<Option<User>, UserError> {
+            self.record("get_user")?;
+            Ok(self.users.lock().unwrap().get(&id).filter(|user| !user.is_deleted()).cloned())
+        }
+
+        async fn get_user_by_uuid(&self, uuid: Uuid) -> Result<Option<User>, UserError> {
+            self.record("get_user_by_uuid")?;
+            Ok(self.collect(|user| !user.is_deleted() && user.uuid == uuid).pop())
+        }
+
+        async fn get_users(&self, ids: &[u32]) -> Result<Vec<Option<User>>, UserError> {
+            self.record("get_users")?;
+            let users = self.users.lock().unwrap();
+            Ok(ids
+                .iter()
+                .map(|id| users.get(id).filter(|user| !user.is_deleted()).cloned())
+                .collect())
+        }
+
+        async fn create_user(&self, user: User) -> Result<User, UserError> {
+            self.record("create_user")?;
+            self.insert_new(user)
+        }
+
+        async fn register_user(&self, new_user: NewUser, hasher: &dyn PasswordHasher) -> Result<User, UserError> {
+            self.record("register_user")?;
+            self.insert_new(new_user.into_user(hasher)?)
+        }
+
+        async fn get_or_create_by_email(
+            &self,
+            new_user: NewUser,
+            hasher: &dyn PasswordHasher,
+        ) -> Result<(User, bool), UserError> {
+            self.record("get_or_create_by_email")?;
+            let email = utils::normalize_email(&new_user.email);
+
+            let mut users = self.users.lock().unwrap();
+            if let Some(existing) = users.values().find(|existing| !existing.is_deleted() && existing.email == email) {
+                return Ok((existing.clone(), false));
+            }
+            insert_locked(&mut users, new_user.into_user(hasher)?).map(|created| (created, true))
+        }
+
+        async fn update_user(&self, mut user: User) -> Result<User, UserError> {
+            self.record("update_user")?;
+            user.email = user.email_normalized();
+            user.validate()?;
+            self.modify(user.id, |users, existing| {
+                if existing.version != user.version {
+                    return Err(UserError::Conflict);
+                }
+                if email_taken(users, &user.email, Some(user.id)) {
+                    return Err(UserError::InvalidInput("email already registered".to_string()));
+                }
+                user.version += 1;
+                user.uuid = existing.uuid;
+                user.created_at = existing.created_at;
+                user.updated_at = SystemTime::now();
+                *existing = user.clone();
+                Ok(user)
+            })
+        }
+
+        async fn delete_user(&self, id: u32) -> Result<(), UserError> {
+            self.record("delete_user")?;
+            self.users.lock().unwrap().remove(&id).map(|_| ()).ok_or(UserError::NotFound)
+        }
+
+        async fn list_users(&self) -> Result<Vec<User>, UserError> {
+            self.record("list_users")?;
+            Ok(self.collect(|user| !user.is_deleted()))
+        }
+
+        async fn list_users_paged(&self, offset: usize, limit: usize) -> Result<Page<User>, UserError> {
+            self.record("list_users_paged")?;
+            let users = self.collect(|user| !user.is_deleted());
+            let total = users.len();
+            let items: Vec<User> = users.into_iter().skip(offset).take(limit).collect();
+            let has_more = offset.saturating_add(items.len()) < total;
+            Ok(Page { items, total, has_more })
+        }
+
+        async fn list_users_after(&self, after_id: Option<u32>, limit: usize) -> Result<Vec<User>, UserError> {
+            self.record("list_users_after")?;
+            let after_id = after_id.unwrap_or(0);
+            let mut users = self.collect(|user| !user.is_deleted() && user.id > after_id);
+            users.truncate(limit);
+            Ok(users)
+        }
+
+        async fn find_by_email(&self, email: &str) -> Result<Option<User>, UserError> {
+            self.record("find_by_email")?;
+            let email = utils::normalize_email(email);
+            Ok(self.collect(|user| !user.is_deleted() && user.email == email).pop())
+        }
+
+        async fn create_users(&self, users: Vec<User>) -> Result<BatchResult, UserError> {
+            self.record("create_users")?;
+            let mut result = BatchResult::default();
+            for (index, user) in users.into_iter().enumerate() {
+                match self.insert_new(user) {
+                    Ok(created) => result.created.push(created),
+                    Err(e) => result.failed.push((index, e)),
+                }
+            }
+            Ok(result)
+        }
+
+        async fn soft_delete_user(&self, id: u32) -> Result<(), UserError> {
+            self.record("soft_delete_user")?;
+            self.modify(id, |_, user| {
+                user.deleted_at = Some(SystemTime::now());
+                Ok(())
+            })
+        }
+
+        async fn list_users_including_deleted(&self) -> Result<Vec<User>, UserError> {
+            self.record("list_users_including_deleted")?;
+            Ok(self.collect(|_| true))
+        }
+
+        async fn search_users(&self, query: UserQuery) -> Result<Vec<User>, UserError> {
+            self.record("search_users")?;
+            let users = self.collect(|user| !user.is_deleted() && query.matches(user));
+            Ok(query.order_and_page(users))
+        }
+
+        async fn count_users(&self) -> Result<usize, UserError> {
+            self.record("count_users")?;
+            Ok(self.collect(|user| !user.is_deleted()).len())
+        }
+
+        async fn count_all(&self) -> Result<usize, UserError> {
+            self.record("count_all")?;
+            Ok(self.users.lock().unwrap().len())
+        }
+
+        async fn change_email(&self, id: u32, new_email: String) -> Result<User, UserError> {
+            self.record("change_email")?;
+            let new_email = utils::normalize_email(&new_email);
+            if !utils::is_valid_email(&new_email) {
+                return Err(UserError::InvalidInput("Invalid email format".to_string()));
+            }
+            self.modify(id, |users, user| {
+                if email_taken(users, &new_email, Some(id)) {
+                    return Err(UserError::InvalidInput("email already registered".to_string()));
+                }
+                user.email = new_email;
+                user.version += 1;
+                user.updated_at = SystemTime::now();
+                Ok(user.clone())
+            })
+        }
+
+        async fn health_check(&self) -> Result<(), UserError> {
+            self.record("health_check")
+        }
+
+        async fn list_users_by_status(&self, status: Status) -> Result<Vec<User>, UserError> {
+            self.record("list_users_by_status")?;
+            Ok(self.collect(|user| !user.is_deleted() && user.status == status))
+        }
+
+        async fn patch_user(&self, id: u32, patch: UserPatch) -> Result<User, UserError> {
+            self.record("patch_user")?;
+            self.modify(id, |users, user| {
+                let mut patched = user.clone();
+                patch.apply_to(&mut patched);
+                patched.validate()?;
+                if email_taken(users, &patched.email, Some(id)) {
+                    return Err(UserError::InvalidInput("email already registered".to_string()));
+                }
+                patched.version += 1;
+                patched.updated_at = SystemTime::now();
+                *user = patched.clone();
+                Ok(patched)
+            })
+        }
+
+        async fn set_user_password(
+            &self,
+            id: u32,
+            new_password: &str,
+            hasher: &dyn PasswordHasher,
+        ) -> Result<(), UserError> {
+            self.record("set_user_password")?;
+            auth::validate_password_strength(new_password)?;
+            let password_hash = hasher.hash(new_password)?;
+            self.modify(id, |_, user| {
+                user.password_hash = password_hash;
+                user.version += 1;
+                user.updated_at = SystemTime::now();
+                Ok(())
+            })
+        }
+
+        async fn mark_email_verified(&self, id: u32) -> Result<User, UserError> {
+            self.record("mark_email_verified")?;
+            self.modify(id, |_, user| {
+                user.email_verified = true;
+                user.version += 1;
+                user.updated_at = SystemTime::now();
+                Ok(user.clone())
+            })
+        }
+
+        async fn set_active(&self, id: u32, active: bool) -> Result<User, UserError> {
+            self.record("set_active")?;
+            self.modify(id, |_, user| {
+                user.active = active;
+                user.version += 1;
+                user.updated_at = SystemTime::now();
+                Ok(user.clone())
+            })
+        }
+    }
+}
+
+/// Serde helpers that encode SystemTime as an RFC3339 string
+mod rfc3339 {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::SystemTime;
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&humantime::format_rfc3339(*time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        humantime::parse_rfc3339(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+// Helper functions
+fn default_max_connections() -> u32 {
+    100
+}
+
+fn default_access_token_ttl_secs() -> u64 {
+    auth::ACCESS_TOKEN_TTL.as_secs()
+}
+
+fn default_refresh_token_ttl_secs() -> u64 {
+    auth::REFRESH_TOKEN_TTL.as_secs()
+}
+
+fn default_timestamp() -> SystemTime {
+    UNIX_EPOCH
+}
+
+fn default_active() -> bool {
+    true
+}
+
+/// Returns the JSON schema of the serialized User
+#[cfg(feature = "schemars")]
+pub fn schema_for_user() -> serde_json::Value {
+    serde_json::to_value(schemars::schema_for!(User)).expect("schema serializes to JSON")
+}
+
+// Macros
+macro_rules! log_user_action {
+    ($action:expr, $user:expr) => {
+        UserActionEvent::new($action, $user.id).log();
+    };
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_user_creation() {
+        let service = UserServiceImpl::new();
+        let user = User::new("John Doe".to_string(), "john@example.com".to_string());
+        
+        let created_user = service.create_user(user).await.unwrap();
+        assert_eq!(created_user.name, "John Doe");
+        assert_eq!(created_user.email, "john@example.com");
+        assert!(created_user.id > 0);
+    }
+
+    #[test]
+    fn test_user_validation() {
+        let mut user = User::new("".to_string(), "john@example.com".to_string());
+        assert!(user.validate().is_err());
+        
+        user.name = "John".to_string();
+        assert!(user.validate().is_ok());
+    }
+
+    #[test]
+    fn test_status_methods() {
+        assert!(Status::Completed.is_completed());
+        assert!(Status::Pending.is_active());
+        assert_eq!(Status::InProgress.as_str(), "in_progress");
+    }
+
+    #[test]
+    fn test_password_verification() {
+        let hasher = PlaintextHasher;
+        let mut user = User::new("John".to_string(), "john@example.com".to_string());
+        assert!(!user.verify_password("secret123", &hasher).unwrap());
+
+        user.set_password("secret123", &hasher).unwrap();
+        assert!(user.verify_password("secret123", &hasher).unwrap());
+        assert!(!user.verify_password("wrong", &hasher).unwrap());
+    }
+
+    const TEST_SECRET: &str = "test-secret-that-is-at-least-32-chars";
+
+    #[test]
+    fn test_token_round_trip() {
+        let token = auth::generate_token(42, &[], TEST_SECRET, Duration::from_secs(60)).unwrap();
+        let claims = auth::validate_token(&token, TEST_SECRET).unwrap();
+        assert_eq!(claims.sub, 42);
+
+        let err = auth::validate_token(&token, "a-different-secret-of-32-characters").unwrap_err();
+        assert!(matches!(err, auth::TokenError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_token_rejects_expired_and_malformed() {
+        let claims = auth::Claims { sub: 42, exp: 1, iat: 0, roles: vec![] };
+        let token = jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &claims,
+            &jsonwebtoken::EncodingKey::from_secret(TEST_SECRET.as_bytes()),
+        )
+        .unwrap();
+        let err = auth::validate_token(&token, TEST_SECRET).unwrap_err();
+        assert!(matches!(err, auth::TokenError::Expired));
+
+        let err = auth::validate_token("not.a.token", TEST_SECRET).unwrap_err();
+        assert!(matches!(err, auth::TokenError::Malformed));
+    }
+
+    #[cfg(feature = "argon2")]
+    #[test]
+    fn test_argon2_password_verification() {
+        let mut user = User::new("John".to_string(), "john@example.com".to_string());
+        user.set_password("secret123", &Argon2Hasher::new()).unwrap();
+
+        // A fresh hasher stands in for a restarted process
+        let hasher = Argon2Hasher::new();
+        assert!(user.verify_password("secret123", &hasher).unwrap());
+        assert!(!user.verify_password("wrong", &hasher).unwrap());
+    }
+
+    #[cfg(feature = "argon2")]
+    #[tokio::test]
+    async fn test_authenticate_rehashes_outdated_password() {
+        let service = UserServiceImpl::new();
+        let old = Argon2Hasher::with_params(8, 1, 1).unwrap();
+        let user = User::builder(&old).name("Alice").email("alice@example.com").password("secret123").build().unwrap();
+        let created = service.create_user(user).await.unwrap();
+
+        let current = Argon2Hasher::with_params(16, 2, 1).unwrap();
+        assert!(current.needs_rehash(&created.password_hash));
+        let limiter = auth::RateLimiter::new(auth::RateLimitConfig::default());
+        let result = auth::authenticate_user(&service, &current, &limiter, "alice@example.com", "secret123").await;
+        assert!(result.unwrap().is_some());
+
+        let stored = service.get_user(created.id).await.unwrap().unwrap();
+        assert_ne!(stored.password_hash, created.password_hash);
+        assert!(!current.needs_rehash(&stored.password_hash));
+        assert!(stored.verify_password("secret123", &current).unwrap());
+    }
+
+    #[cfg(feature = "argon2")]
+    #[test]
+    fn test_argon2_low_cost_round_trip() {
+        let config = Config::builder()
+            .database_url("postgres://localhost/app")
+            .jwt_secret(TEST_SECRET)
+            .password_cost(1)
+            .build()
+            .unwrap();
+        let hasher = Argon2Hasher::from_config(&config).unwrap();
+
+        let hash = hasher.hash("secret123").unwrap();
+        assert!(hasher.verify("secret123", &hash).unwrap());
+
+        // Cost parameters are embedded in the hash, so a differently tuned hasher still verifies it
+        let fast = Argon2Hasher::with_params(8, 1, 1).unwrap();
+        assert!(fast.verify("secret123", &hash).unwrap());
+        assert!(fast.verify("secret123", &fast.hash("secret123").unwrap()).unwrap());
+    }
+
+    async fn create_test_users(service: &UserServiceImpl, count: usize) {
+        for i in 0..count {
+            let user = User::new(format!("User {}", i), format!("user{}@example.com", i));
+            service.create_user(user).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_users_paged_partial_final_page() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 5).await;
+
+        let page = service.list_users_paged(0, 2).await.unwrap();
+        assert_eq!(page.items.iter().map(|u| u.id).collect::<Vec<_>>(), vec![1, 2]);
+        assert!(page.has_more);
+
+        let page = service.list_users_paged(4, 2).await.unwrap();
+        assert_eq!(page.items.len(), 1);
+        assert_eq!(page.items[0].id, 5);
+        assert_eq!(page.total, 5);
+        assert!(!page.has_more);
+    }
+
+    #[tokio::test]
+    async fn test_list_users_paged_past_end() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 3).await;
+
+        let page = service.list_users_paged(10, 2).await.unwrap();
+        assert!(page.items.is_empty());
+        assert_eq!(page.total, 3);
+        assert!(!page.has_more);
+    }
+
+    #[tokio::test]
+    async fn test_list_users_after_keyset_pages() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 5).await;
+
+        let first = service.list_users_after(None, 3).await.unwrap();
+        let cursor = first.last().map(|user| user.id);
+        let second = service.list_users_after(cursor, 3).await.unwrap();
+
+        let ids: Vec<u32> = first.iter().chain(&second).map(|user| user.id).collect();
+        assert_eq!(ids, vec![1, 2, 3, 4, 5]);
+        assert!(service.list_users_after(Some(5), 3).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_user_builder() {
+        let hasher = PlaintextHasher;
+        let user = User::builder(&hasher)
+            .name("John")
+            .email("john@example.com")
+            .password("secret123")
+            .build()
+            .unwrap();
+
+        assert_eq!(user.name, "John");
+        assert_eq!(user.email, "john@example.com");
+        assert!(user.verify_password("secret123", &hasher).unwrap());
+    }
+
+    #[test]
+    fn test_user_builder_rejects_invalid_email() {
+        let result = User::builder(&PlaintextHasher)
+            .name("John")
+            .email("john.example.com")
+            .password("secret123")
+            .build();
+
+        assert!(matches!(result, Err(UserError::InvalidInput(_))));
+    }
+
+    #[tokio::test]
+    async fn test_find_by_email() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 2).await;
+
+        let user = service.find_by_email("user1@example.com").await.unwrap().unwrap();
+        assert_eq!(user.name, "User 1");
+        assert!(service.find_by_email("missing@example.com").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_find_by_email_after_email_change() {
+        let service = UserServiceImpl::new();
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+        let mut user = service.create_user(user).await.unwrap();
+
+        user.email = "johnny@example.com".to_string();
+        service.update_user(user).await.unwrap();
+
+        assert!(service.find_by_email("john@example.com").await.unwrap().is_none());
+        let found = service.find_by_email("johnny@example.com").await.unwrap().unwrap();
+        assert_eq!(found.name, "John");
+    }
+
+    #[tokio::test]
+    async fn test_cache_ttl_expiry() {
+        let manager = UserManager::with_ttl(UserServiceImpl::new(), Duration::from_millis(50));
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+        let mut user = manager.service.create_user(user).await.unwrap();
+        assert_eq!(manager.get_user_cached(user.id).await.unwrap().unwrap().name, "John");
+
+        user.name = "Johnny".to_string();
+        manager.service.update_user(user.clone()).await.unwrap();
+
+        // Fresh entry is still served from the cache
+        assert_eq!(manager.get_user_cached(user.id).await.unwrap().unwrap().name, "John");
+
+        tokio::time::sleep(Duration::from_millis(80)).await;
+        assert_eq!(manager.get_user_cached(user.id).await.unwrap().unwrap().name, "Johnny");
+    }
+
+    #[tokio::test]
+    async fn test_create_users_partial_failure() {
+        let service = UserServiceImpl::new();
+        let users = vec![
+            User::new("Alice".to_string(), "alice@example.com".to_string()),
+            User::new("".to_string(), "nobody@example.com".to_string()),
+            User::new("Bob".to_string(), "bob@example.com".to_string()),
+        ];
+
+        let result = service.create_users(users).await.unwrap();
+        assert_eq!(result.created.len(), 2);
+        assert_eq!(result.failed.len(), 1);
+        assert_eq!(result.failed[0].0, 1);
+        assert!(matches!(result.failed[0].1, UserError::InvalidInput(_)));
+        assert_eq!(service.list_users().await.unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_generate_id_unique_across_threads() {
+        let handles: Vec<_> = (0..8)
+            .map(|_| std::thread::spawn(|| (0..1000).map(|_| utils::generate_id()).collect::<Vec<_>>()))
+            .collect();
+
+        let mut ids = std::collections::HashSet::new();
+        for handle in handles {
+            for id in handle.join().unwrap() {
+                assert!(ids.insert(id), "duplicate id {}", id);
+            }
+        }
+        assert_eq!(ids.len(), 8000);
+    }
+
+    #[test]
+    fn test_status_transitions() {
+        use Status::*;
+        let cases = [
+            (Pending, InProgress, true),
+            (InProgress, Completed, true),
+            (InProgress, Failed, true),
+            (Failed, Pending, true),
+            (Pending, Completed, false),
+            (Pending, Failed, false),
+            (Completed, Pending, false),
+            (Completed, InProgress, false),
+            (Failed, Completed, false),
+            (InProgress, Pending, false),
+        ];
+
+        for (from, to, allowed) in cases {
+            assert_eq!(from.can_transition_to(to), allowed, "{} -> {}", from, to);
+            assert_eq!(from.try_transition(to).is_ok(), allowed, "{} -> {}", from, to);
+        }
+    }
+
+    #[test]
+    fn test_status_serde() {
+        let json = serde_json::to_string(&Status::InProgress).unwrap();
+        assert_eq!(json, "\"in_progress\"");
+        assert_eq!(json, format!("\"{}\"", Status::InProgress.as_str()));
+        assert_eq!(serde_json::from_str::<Status>(&json).unwrap(), Status::InProgress);
+
+        assert!(serde_json::from_str::<Status>("\"archived\"").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_soft_delete_user() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 2).await;
+
+        service.soft_delete_user(1).await.unwrap();
+
+        assert!(service.get_user(1).await.unwrap().is_none());
+        assert_eq!(service.list_users().await.unwrap().len(), 1);
+
+        let all = service.list_users_including_deleted().await.unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(all.iter().any(|user| user.id == 1 && user.is_deleted()));
+
+        assert!(matches!(service.soft_delete_user(1).await, Err(UserError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_stream_users_matches_list_users() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 5).await;
+
+        let streamed: Vec<User> = service.stream_users_batched(2).try_collect().await.unwrap();
+        let mut listed = service.list_users().await.unwrap();
+        listed.sort_by_key(|user| user.id);
+
+        let streamed_ids: Vec<u32> = streamed.iter().map(|user| user.id).collect();
+        let listed_ids: Vec<u32> = listed.iter().map(|user| user.id).collect();
+        assert_eq!(streamed_ids, listed_ids);
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_with_mixed_case_email() {
+        let service = UserServiceImpl::new();
+        let hasher = PlaintextHasher;
+        let user = User::builder(&hasher)
+            .name("Alice")
+            .email("alice@example.com")
+            .password("secret123")
+            .build()
+            .unwrap();
+        service.create_user(user).await.unwrap();
+
+        let limiter = auth::RateLimiter::new(auth::RateLimitConfig::default());
+        let found = auth::authenticate_user(&service, &hasher, &limiter, " Alice@Example.COM ", "secret123")
+            .await
+            .unwrap();
+        assert_eq!(found.unwrap().email, "alice@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_create_user_normalizes_email() {
+        let service = UserServiceImpl::new();
+        let user = User::new("Bob".to_string(), "Bob@Example.com".to_string());
+        let created = service.create_user(user).await.unwrap();
+
+        assert_eq!(created.email, "bob@example.com");
+        assert!(service.find_by_email("BOB@example.com").await.unwrap().is_some());
+    }
+
+    #[test]
+    fn test_user_error_codes() {
+        let cases = [
+            (UserError::NotFound, "not_found", 404),
+            (UserError::InvalidInput("bad".to_string()), "invalid_input", 400),
+            (UserError::Database(DatabaseError::ConnectionFailed), "database_error", 500),
+            (UserError::Authentication, "authentication", 401),
+            (UserError::Hashing("bad".to_string()), "hashing_error", 500),
+            (UserError::Conflict, "conflict", 409),
+            (UserError::RateLimited, "rate_limited", 429),
+            (UserError::Unavailable, "unavailable", 503),
+            (UserError::Config(ConfigError::Invalid("bad".to_string())), "config_error", 500),
+            (UserError::Locked, "locked", 423),
+        ];
+
+        for (error, code, status) in cases {
+            assert_eq!(error.code(), code);
+            assert_eq!(error.http_status(), status);
+        }
+    }
+
+    #[test]
+    fn test_user_error_json_body() {
+        let json = serde_json::to_value(UserError::NotFound).unwrap();
+        assert_eq!(json, serde_json::json!({ "code": "not_found", "message": "User not found" }));
+
+        let body = UserError::Database(DatabaseError::QueryFailed("syntax error at \"FROM\"".to_string())).to_error_body();
+        assert_eq!(body.code, "database_error");
+        assert_eq!(body.message, "Internal server error");
+    }
+
+    #[tokio::test]
+    async fn test_update_user_rejects_stale_version() {
+        let service = UserServiceImpl::new();
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+        let created = service.create_user(user).await.unwrap();
+
+        let mut fresh = created.clone();
+        fresh.name = "Johnny".to_string();
+        let updated = service.update_user(fresh).await.unwrap();
+        assert_eq!(updated.version, created.version + 1);
+
+        let mut stale = created.clone();
+        stale.name = "Jack".to_string();
+        assert!(matches!(service.update_user(stale).await, Err(UserError::Conflict)));
+
+        let stored = service.get_user(created.id).await.unwrap().unwrap();
+        assert_eq!(stored.name, "Johnny");
+        assert_eq!(stored.version, updated.version);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rolls_back_on_error() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 1).await;
+
+        let result: Result<(), UserError> = service
+            .with_transaction(|svc| async move {
+                let user = User::new("Temp".to_string(), "temp@example.com".to_string());
+                svc.create_user(user).await?;
+                Err(UserError::InvalidInput("role assignment failed".to_string()))
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(service.list_users().await.unwrap().len(), 1);
+        assert!(service.find_by_email("temp@example.com").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cache_stats() {
+        let manager = UserManager::new(UserServiceImpl::new());
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+        let user = manager.service.create_user(user).await.unwrap();
+
+        manager.get_user_cached(user.id).await.unwrap();
+        manager.get_user_cached(user.id).await.unwrap();
+
+        let stats = manager.cache_stats().await;
+        assert_eq!(stats, CacheStats { hits: 1, misses: 1, size: 1 });
+    }
+
+    fn config_toml(max_connections: u32) -> String {
+        format!(
+            "port = 8080\ndatabase_url = \"postgres://localhost/app\"\njwt_secret = \"{}\"\nmax_connections = {}\n",
+            TEST_SECRET, max_connections
+        )
+    }
+
+    #[tokio::test]
+    async fn test_config_watch_file_publishes_changes() {
+        let path = std::env::temp_dir().join(format!("config-watch-{}.toml", std::process::id()));
+        std::fs::write(&path, config_toml(10)).unwrap();
+
+        let (_watcher, mut rx) = Config::watch_file_with_interval(&path, Duration::from_millis(20)).unwrap();
+        assert_eq!(rx.borrow().max_connections, 10);
+
+        std::fs::write(&path, config_toml(50)).unwrap();
+        tokio::time::timeout(Duration::from_secs(2), rx.changed()).await.unwrap().unwrap();
+        assert_eq!(rx.borrow().max_connections, 50);
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_require_role() {
+        use auth::Role;
+
+        let token = auth::generate_token(1, &[Role::Admin, Role::User], TEST_SECRET, Duration::from_secs(60)).unwrap();
+        let admin = auth::validate_token(&token, TEST_SECRET).unwrap();
+        assert!(auth::require_role(&admin, Role::Admin).is_ok());
+
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+        let token = auth::generate_token(2, &user.roles, TEST_SECRET, Duration::from_secs(60)).unwrap();
+        let claims = auth::validate_token(&token, TEST_SECRET).unwrap();
+        assert!(auth::require_role(&claims, Role::User).is_ok());
+        assert!(matches!(auth::require_role(&claims, Role::Admin), Err(UserError::Authentication)));
+    }
+
+    #[test]
+    fn test_require_admin() {
+        let mut user = User::new("John".to_string(), "john@example.com".to_string());
+        assert!(!user.is_admin());
+        assert!(matches!(auth::require_admin(&user), Err(UserError::Authentication)));
+
+        user.roles.push(auth::Role::Admin);
+        assert!(user.is_admin());
+        assert!(auth::require_admin(&user).is_ok());
+    }
+
+    #[test]
+    fn test_refresh_token_rotation() {
+        let store = auth::RefreshStore::new();
+        let refresh = auth::generate_refresh_token(7, &[auth::Role::User], TEST_SECRET, &store).unwrap();
+
+        let (access, rotated) = auth::exchange_refresh_token(&refresh, TEST_SECRET, &store).unwrap();
+        assert_eq!(auth::validate_token(&access, TEST_SECRET).unwrap().sub, 7);
+        assert_ne!(rotated, refresh);
+
+        let err = auth::exchange_refresh_token(&refresh, TEST_SECRET, &store).unwrap_err();
+        assert!(matches!(err, auth::TokenError::Revoked));
+
+        let other = auth::RefreshStore::new();
+        assert!(matches!(
+            auth::exchange_refresh_token(&rotated, TEST_SECRET, &other),
+            Err(auth::TokenError::Revoked)
+        ));
+        assert!(auth::exchange_refresh_token(&rotated, TEST_SECRET, &store).is_ok());
+    }
+
+    #[test]
+    fn test_refresh_store_evicts_expired_tokens() {
+        let clock = Arc::new(TestClock::new());
+        let store = auth::RefreshStore::with_clock(clock.clone());
+        auth::generate_refresh_token_with_ttl(1, &[], TEST_SECRET, Duration::from_secs(60), &store).unwrap();
+        auth::generate_refresh_token_with_ttl(2, &[], TEST_SECRET, Duration::from_secs(600), &store).unwrap();
+        assert_eq!(store.len(), 2);
+
+        clock.advance(Duration::from_secs(61));
+        assert_eq!(store.len(), 1);
+        clock.advance(Duration::from_secs(600));
+        assert!(store.is_empty());
+    }
+
+    #[cfg(feature = "postgres")]
+    #[tokio::test]
+    async fn test_pg_user_service_round_trip() {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            return;
+        };
+        let service = postgres::PgUserService::connect(&url).await.unwrap();
+        service.migrate().await.unwrap();
+
+        let email = format!("pg-{}-{}@example.com", std::process::id(), utils::generate_id());
+        let user = User::new("Pg User".to_string(), email.clone());
+        let mut created = service.create_user(user.clone()).await.unwrap();
+        assert_eq!(service.find_by_email(&email).await.unwrap().unwrap().id, created.id);
+
+        let duplicate = service.create_user(user).await;
+        assert!(matches!(duplicate, Err(UserError::InvalidInput(msg)) if msg == "email already registered"));
+
+        created.name = "Renamed".to_string();
+        let updated = service.update_user(created.clone()).await.unwrap();
+        assert_eq!(updated.version, created.version + 1);
+        assert!(matches!(service.update_user(created.clone()).await, Err(UserError::Conflict)));
+
+        service.delete_user(created.id).await.unwrap();
+        assert!(service.get_user(created.id).await.unwrap().is_none());
+    }
+
+    struct CountingRepository {
+        values: RwLock<HashMap<u32, String>>,
+        gets: AtomicU64,
+    }
+
+    #[async_trait]
+    impl Repository<u32, String> for CountingRepository {
+        async fn get(&self, key: &u32) -> Result<Option<String>, UserError> {
+            self.gets.fetch_add(1, Ordering::Relaxed);
+            Ok(self.values.read().await.get(key).cloned())
+        }
+
+        async fn insert(&self, key: u32, value: String) -> Result<(), UserError> {
+            self.values.write().await.insert(key, value);
+            Ok(())
+        }
+
+        async fn remove(&self, key: &u32) -> Result<Option<String>, UserError> {
+            Ok(self.values.write().await.remove(key))
+        }
+
+        async fn list(&self) -> Result<Vec<String>, UserError> {
+            Ok(self.values.read().await.values().cloned().collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_caching_repository_reuse() {
+        let inner = CountingRepository {
+            values: RwLock::new(HashMap::from([(1, "one".to_string())])),
+            gets: AtomicU64::new(0),
+        };
+        let repo = CachingRepository::new(inner, Duration::from_secs(60));
+
+        assert_eq!(repo.get(&1).await.unwrap().as_deref(), Some("one"));
+        assert_eq!(repo.get(&1).await.unwrap().as_deref(), Some("one"));
+        assert_eq!(repo.inner().gets.load(Ordering::Relaxed), 1);
+
+        repo.remove(&1).await.unwrap();
+        assert!(repo.get(&1).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_user_service_as_repository() {
+        let repo = CachingRepository::new(UserServiceImpl::new(), Duration::from_secs(60));
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+        repo.insert(5, user).await.unwrap();
+
+        assert_eq!(repo.get(&5).await.unwrap().unwrap().name, "John");
+        assert!(repo.inner().find_by_email("john@example.com").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_repository_insert_rejects_taken_email() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 2).await;
+
+        let user = User::new("Intruder".to_string(), "user1@example.com".to_string());
+        assert!(matches!(
+            Repository::insert(&service, 7, user).await,
+            Err(UserError::InvalidInput(ref msg)) if msg == "email already registered"
+        ));
+        assert!(service.get_user(7).await.unwrap().is_none());
+        assert_eq!(service.find_by_email("user1@example.com").await.unwrap().unwrap().id, 2);
+
+        let renamed = User::new("User 1 renamed".to_string(), "user1@example.com".to_string());
+        Repository::insert(&service, 2, renamed).await.unwrap();
+        assert_eq!(service.get_user(2).await.unwrap().unwrap().name, "User 1 renamed");
+    }
+
+    #[test]
+    fn test_is_valid_email() {
+        let cases = [
+            ("good@example.com", true),
+            ("good@example.co.uk", true),
+            ("first.last+tag@sub-domain.example.org", true),
+            ("a@b", false),
+            ("a b@c.com", false),
+            ("@.", false),
+            ("@example.com", false),
+            ("a@@example.com", false),
+            ("a@example.com trailing", false),
+            ("a@example.", false),
+            ("a@.example.com", false),
+        ];
+
+        for (email, valid) in cases {
+            assert_eq!(utils::is_valid_email(email), valid, "{}", email);
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn test_create_user_emits_span() {
+        use std::sync::Mutex;
+        use tracing_subscriber::layer::{Context, SubscriberExt};
+        use tracing_subscriber::Layer;
+
+        struct SpanNames(Arc<Mutex<Vec<&'static str>>>);
+
+        impl<S: tracing::Subscriber> Layer<S> for SpanNames {
+            fn on_new_span(&self, attrs: &tracing::span::Attributes<'_>, _: &tracing::span::Id, _: Context<'_, S>) {
+                self.0.lock().unwrap().push(attrs.metadata().name());
+            }
+        }
+
+        let names = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry().with(SpanNames(names.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let manager = UserManager::new(UserServiceImpl::new());
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+        let user = manager.service.create_user(user).await.unwrap();
+        manager.get_user_cached(user.id).await.unwrap();
+
+        let names = names.lock().unwrap();
+        assert!(names.contains(&"create_user"));
+        assert!(names.contains(&"get_user_cached"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_sharded_concurrent_writers() {
+        let service = Arc::new(UserServiceImpl::new_with_shards(8));
+
+        let writers: Vec<_> = (0..32)
+            .map(|writer| {
+                let service = service.clone();
+                tokio::spawn(async move {
+                    for i in 0..25 {
+                        let email = format!("writer{}-{}@example.com", writer, i);
+                        let user = User::new(format!("Writer {}", writer), email);
+                        service.create_user(user).await.unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap();
+        }
+
+        let users = service.list_users().await.unwrap();
+        assert_eq!(users.len(), 800);
+        let ids: std::collections::HashSet<u32> = users.iter().map(|user| user.id).collect();
+        assert_eq!(ids.len(), 800);
+        for user in &users {
+            let found = service.find_by_email(&user.email).await.unwrap().unwrap();
+            assert_eq!(found.id, user.id);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timestamps_set_on_create_and_update() {
+        let service = UserServiceImpl::new();
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+        assert_eq!(user.created_at, UNIX_EPOCH);
+
+        let created = service.create_user(user).await.unwrap();
+        assert!(created.created_at > UNIX_EPOCH);
+        assert_eq!(created.updated_at, created.created_at);
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        let updated = service.update_user(created.clone()).await.unwrap();
+        assert_eq!(updated.created_at, created.created_at);
+        assert!(updated.updated_at > updated.created_at);
+    }
+
+    #[test]
+    fn test_timestamps_serialize_as_rfc3339() {
+        let mut user = User::new("John".to_string(), "john@example.com".to_string());
+        user.created_at = UNIX_EPOCH + Duration::from_secs(86_400);
+
+        let json = serde_json::to_value(&user).unwrap();
+        assert_eq!(json["created_at"], "1970-01-02T00:00:00Z");
+
+        let parsed: User = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed.created_at, user.created_at);
+    }
+
+    async fn create_named_users(service: &UserServiceImpl, users: &[(&str, &str)]) {
+        for (name, email) in users {
+            service.create_user(User::new(name.to_string(), email.to_string())).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_users_by_name() {
+        let service = UserServiceImpl::new();
+        create_named_users(&service, &[
+            ("Alice Smith", "alice@example.com"),
+            ("Bob Jones", "bob@other.org"),
+            ("Carol Smithers", "carol@example.com"),
+        ])
+        .await;
+
+        let query = UserQuery { name_contains: Some("Smith".to_string()), ..Default::default() };
+        let names: Vec<String> = service.search_users(query).await.unwrap().into_iter().map(|u| u.name).collect();
+        assert_eq!(names, vec!["Alice Smith", "Carol Smithers"]);
+
+        let query = UserQuery { limit: Some(2), ..Default::default() };
+        assert_eq!(service.search_users(query).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_search_users_by_email_domain() {
+        let service = UserServiceImpl::new();
+        create_named_users(&service, &[
+            ("Alice", "alice@example.com"),
+            ("Bob", "bob@other.org"),
+            ("Dave", "dave@notexample.com"),
+        ])
+        .await;
+
+        let query = UserQuery { email_domain: Some("example.com".to_string()), ..Default::default() };
+        let found = service.search_users(query).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].email, "alice@example.com");
+    }
+
+    #[derive(Default)]
+    struct CountingListener {
+        created: AtomicU64,
+        deleted: AtomicU64,
+    }
+
+    impl EventListener for CountingListener {
+        fn on_created(&self, _user: &User) {
+            self.created.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn on_deleted(&self, _id: u32) {
+            self.deleted.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_listener_on_created() {
+        let listener = Arc::new(CountingListener::default());
+        let mut service = UserServiceImpl::new();
+        service.add_listener(listener.clone());
+
+        create_test_users(&service, 3).await;
+        service
+            .create_users(vec![User::new("".to_string(), "bad@example.com".to_string())])
+            .await
+            .unwrap();
+        service.delete_user(1).await.unwrap();
+
+        assert_eq!(listener.created.load(Ordering::Relaxed), 3);
+        assert_eq!(listener.deleted.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_user_rate_limited() {
+        let service = UserServiceImpl::new();
+        let hasher = PlaintextHasher;
+        let user = User::builder(&hasher).name("Alice").email("alice@example.com").password("secret123").build().unwrap();
+        service.create_user(user).await.unwrap();
+
+        let clock = Arc::new(TestClock::new());
+        let config = auth::RateLimitConfig { max_attempts: 2, window: Duration::from_secs(60) };
+        let limiter = auth::RateLimiter::with_clock(config, clock.clone());
+
+        for _ in 0..2 {
+            let result = auth::authenticate_user(&service, &hasher, &limiter, "alice@example.com", "wrong").await;
+            assert!(result.unwrap().is_none());
+        }
+        let result = auth::authenticate_user(&service, &hasher, &limiter, "alice@example.com", "secret123").await;
+        assert!(matches!(result, Err(UserError::RateLimited)));
+
+        clock.advance(Duration::from_secs(60));
+        let result = auth::authenticate_user(&service, &hasher, &limiter, "alice@example.com", "secret123").await;
+        assert!(result.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_rate_limiter_evicts_idle_buckets() {
+        let clock = Arc::new(TestClock::new());
+        let config = auth::RateLimitConfig { max_attempts: 2, window: Duration::from_secs(60) };
+        let limiter = auth::RateLimiter::with_clock(config, clock.clone());
+
+        for i in 0..10 {
+            limiter.check(&format!("client-{}", i)).await.unwrap();
+        }
+        assert_eq!(limiter.tracked_keys().await, 10);
+
+        clock.advance(Duration::from_secs(30));
+        limiter.check("client-0").await.unwrap();
+        clock.advance(Duration::from_secs(30));
+        limiter.check("client-0").await.unwrap();
+        assert_eq!(limiter.tracked_keys().await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_user_locks_account() {
+        let service = UserServiceImpl::new();
+        let hasher = PlaintextHasher;
+        let user = User::builder(&hasher).name("Alice").email("alice@example.com").password("secret123").build().unwrap();
+        service.create_user(user).await.unwrap();
+
+        let clock = Arc::new(TestClock::new());
+        let config = auth::LockoutConfig { max_failures: 3, duration: Duration::from_secs(300) };
+        let lockout = auth::AccountLockout::with_clock(config, clock.clone());
+        let limiter = auth::RateLimiter::new(auth::RateLimitConfig { max_attempts: 100, window: Duration::from_secs(60) });
+
+        for _ in 0..3 {
+            let result =
+                auth::authenticate_user_with_lockout(&service, &hasher, &limiter, &lockout, "alice@example.com", "wrong").await;
+            assert!(result.unwrap().is_none());
+        }
+        let result =
+            auth::authenticate_user_with_lockout(&service, &hasher, &limiter, &lockout, "alice@example.com", "secret123").await;
+        assert!(matches!(result, Err(UserError::Locked)));
+
+        clock.advance(Duration::from_secs(300));
+        let result =
+            auth::authenticate_user_with_lockout(&service, &hasher, &limiter, &lockout, "ALICE@example.com", "secret123").await;
+        assert!(result.unwrap().is_some());
+    }
+
+    #[test]
+    fn test_verify_password_uses_constant_time_eq() {
+        /// Records every password it hashes, relying on the default `verify`
+        #[derive(Default)]
+        struct RecordingHasher {
+            hashed: Mutex<Vec<String>>,
+        }
+
+        impl PasswordHasher for RecordingHasher {
+            fn hash(&self, password: &str) -> Result<String, UserError> {
+                self.hashed.lock().unwrap().push(password.to_string());
+                PlaintextHasher.hash(password)
+            }
+        }
+
+        let hasher = RecordingHasher::default();
+        let mut user = User::new("John".to_string(), "john@example.com".to_string());
+        user.set_password("secret123", &hasher).unwrap();
+
+        assert!(user.verify_password("secret123", &hasher).unwrap());
+        assert!(!user.verify_password("secreT", &hasher).unwrap());
+        assert!(!user.verify_password("secret-but-longer", &hasher).unwrap());
+        assert_eq!(
+            *hasher.hashed.lock().unwrap(),
+            ["secret123", "secret123", "secreT", "secret-but-longer"]
+        );
+
+        assert!(utils::constant_time_eq("abc", "abc"));
+        assert!(!utils::constant_time_eq("abc", "abd"));
+        assert!(!utils::constant_time_eq("abc", "ab"));
+    }
+
+    #[test]
+    fn test_config_rejects_unknown_fields() {
+        let json = format!(
+            r#"{{"port": 9090, "databse_url": "postgres://localhost/app", "jwt_secret": "{}"}}"#,
+            TEST_SECRET
+        );
+        let err = Config::from_json_str(&json).unwrap_err().to_string();
+        assert!(err.starts_with("Invalid JSON config"));
+        assert!(err.contains("unknown field `databse_url`"));
+
+        let toml = config_toml(10).replace("database_url", "databse_url");
+        let err = Config::from_toml_str(&toml).unwrap_err().to_string();
+        assert!(err.starts_with("Invalid TOML config"));
+        assert!(err.contains("unknown field `databse_url`"));
+    }
+
+    #[test]
+    fn test_config_json_round_trip_hides_secret() {
+        let input = format!(
+            r#"{{"port": 9090, "database_url": "postgres://localhost/app", "jwt_secret": "{}"}}"#,
+            TEST_SECRET
+        );
+        let config = Config::from_json_str(&input).unwrap();
+        assert_eq!(config.port, 9090);
+        assert_eq!(config.jwt_secret, TEST_SECRET);
+        assert_eq!(config.max_connections, default_max_connections());
+
+        let output = config.to_json_string().unwrap();
+        assert!(!output.contains(TEST_SECRET));
+        assert!(!output.contains("jwt_secret"));
+
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(value["port"], 9090);
+        assert_eq!(value["database_url"], "postgres://localhost/app");
+    }
+
+    #[tokio::test]
+    async fn test_count_users() {
+        let service = UserServiceImpl::new();
+        assert_eq!(service.count_users().await.unwrap(), 0);
+
+        create_test_users(&service, 4).await;
+        assert_eq!(service.count_users().await.unwrap(), 4);
+
+        service.soft_delete_user(2).await.unwrap();
+        assert_eq!(service.count_users().await.unwrap(), 3);
+        assert_eq!(service.count_all().await.unwrap(), 4);
+    }
+
+    #[test]
+    fn test_public_user_has_no_password_fields() {
+        let hasher = PlaintextHasher;
+        let user = User::builder(&hasher).name("John").email("john@example.com").password("secret123").build().unwrap();
+
+        let public = user.to_public();
+        assert_eq!(public, PublicUser::from(user.clone()));
+
+        let json = serde_json::to_value(&public).unwrap();
+        let keys: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+        assert_eq!(keys.len(), 5);
+        for key in ["uuid", "name", "email", "created_at", "updated_at"] {
+            assert!(keys.contains(&key), "missing {}", key);
+        }
+        assert!(!json.to_string().contains("password"));
+        assert!(!json.to_string().contains("hashed_secret123"));
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_transient_eventually_succeeds() {
+        let calls = AtomicU32::new(0);
+        let result = utils::retry_on_transient(5, Duration::from_millis(1), || async {
+            if calls.fetch_add(1, Ordering::Relaxed) < 2 {
+                Err(UserError::Database(DatabaseError::ConnectionFailed))
+            } else {
+                Ok(42)
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 42);
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_on_transient_gives_up() {
+        let calls = AtomicU32::new(0);
+        let result: Result<(), UserError> = utils::retry_on_transient(3, Duration::from_millis(1), || async {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Err(UserError::Database(DatabaseError::ConnectionFailed))
+        })
+        .await;
+        assert!(matches!(result, Err(UserError::Database(DatabaseError::ConnectionFailed))));
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+        let calls = AtomicU32::new(0);
+        let result: Result<(), UserError> = utils::retry_on_transient(3, Duration::from_millis(1), || async {
+            calls.fetch_add(1, Ordering::Relaxed);
+            Err(UserError::Database(DatabaseError::QueryFailed("syntax".to_string())))
+        })
+        .await;
+        assert!(matches!(result, Err(UserError::Database(DatabaseError::QueryFailed(_)))));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_csv_round_trip() {
+        let service = UserServiceImpl::new();
+        let mut user = User::new("Alice".to_string(), "alice@example.com".to_string());
+        user.set_password("secret123", &PlaintextHasher).unwrap();
+        let alice = service.create_user(user).await.unwrap();
+        let bob = service
+            .create_user(User::new("Bob, Jr.".to_string(), "bob@example.com".to_string()))
+            .await
+            .unwrap();
+
+        let csv = service.export_users_csv().await.unwrap();
+        assert!(csv.starts_with("id,name,email\n"));
+        assert!(!csv.contains("hashed_secret123"));
+
+        service.delete_user(alice.id).await.unwrap();
+        service.delete_user(bob.id).await.unwrap();
+        assert_eq!(service.count_users().await.unwrap(), 0);
+
+        let result = service.import_users_csv(&csv).await.unwrap();
+        assert!(result.failed.is_empty());
+        let mut users = service.list_users().await.unwrap();
+        users.sort_by_key(|user| user.id);
+        let imported: Vec<(String, String)> = users
+            .into_iter()
+            .map(|user| (user.name, user.email))
+            .collect();
+        assert_eq!(imported, vec![
+            ("Alice".to_string(), "alice@example.com".to_string()),
+            ("Bob, Jr.".to_string(), "bob@example.com".to_string()),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_csv_import_reports_row_failures() {
+        let service = UserServiceImpl::new();
+        let data = "id,name,email\n1,Alice,alice@example.com\nnot-a-number,Bob,bob@example.com\n3,Carol,not-an-email\n";
+
+        let result = service.import_users_csv(data).await.unwrap();
+        assert_eq!(result.created.len(), 1);
+        let failed: Vec<usize> = result.failed.iter().map(|(index, _)| *index).collect();
+        assert_eq!(failed, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_config_builder() {
+        let config = Config::builder()
+            .database_url("postgres://localhost/test")
+            .jwt_secret(TEST_SECRET)
+            .max_connections(10)
+            .build()
+            .unwrap();
+        assert_eq!(config.port, DEFAULT_PORT);
+        assert_eq!(config.database_url, "postgres://localhost/test");
+        assert_eq!(config.max_connections, 10);
+
+        let config = Config::builder()
+            .port(9000)
+            .database_url("postgres://localhost/test")
+            .jwt_secret(TEST_SECRET)
+            .build()
+            .unwrap();
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.max_connections, 100);
+    }
+
+    #[test]
+    fn test_config_builder_rejects_short_secret() {
+        let err = Config::builder()
+            .database_url("postgres://localhost/test")
+            .jwt_secret("too-short")
+            .build()
+            .unwrap_err();
+        assert!(err.to_string().contains("at least 32 characters"));
+    }
+
+    #[test]
+    fn test_config_validation_error_is_typed() {
+        let config = Config::builder().database_url("postgres://localhost/test").jwt_secret(TEST_SECRET).build().unwrap();
+        let invalid = Config { port: 0, ..config };
+
+        let err = invalid.validate().unwrap_err();
+        assert!(matches!(&err, ConfigError::Invalid(msg) if msg == "Port must be greater than 0"));
+        let err = UserError::from(err);
+        assert_eq!(err.code(), "config_error");
+        assert_eq!(err.to_string(), "Configuration error: Port must be greater than 0");
+    }
+
+    #[test]
+    fn test_config_rejects_zero_max_connections() {
+        let err = Config::builder()
+            .database_url("postgres://localhost/test")
+            .jwt_secret(TEST_SECRET)
+            .max_connections(0)
+            .build()
+            .unwrap_err();
+        assert!(matches!(&err, ConfigError::Invalid(msg) if msg == "Max connections must be at least 1"));
+    }
+
+    #[tokio::test]
+    async fn test_register_user_hashes_password() {
+        let service = UserServiceImpl::new();
+        let new_user = NewUser::new("Alice", " Alice@Example.com ", "secret123");
+
+        let created = service.register_user(new_user, &PlaintextHasher).await.unwrap();
+        assert_eq!(created.id, 1);
+        assert_eq!(created.email, "alice@example.com");
+
+        let stored = service.get_user(created.id).await.unwrap().unwrap();
+        assert!(stored.verify_password("secret123", &PlaintextHasher).unwrap());
+        assert!(!stored.verify_password("wrong", &PlaintextHasher).unwrap());
+
+        let debug = format!("{:?}", NewUser::new("Bob", "bob@example.com", "secret123"));
+        assert!(!debug.contains("secret123"));
+    }
+
+    #[test]
+    fn test_password_strength() {
+        let err = auth::validate_password_strength("short").unwrap_err();
+        assert!(matches!(err, UserError::InvalidInput(ref msg) if msg.contains("at least 8")));
+
+        let err = auth::validate_password_strength("longenough").unwrap_err();
+        assert!(matches!(err, UserError::InvalidInput(ref msg) if msg.contains("digit")));
+
+        let err = auth::validate_password_strength("12345678").unwrap_err();
+        assert!(matches!(err, UserError::InvalidInput(ref msg) if msg.contains("letter")));
+
+        assert!(auth::validate_password_strength("hunter2aa").is_ok());
+
+        let mut user = User::new("John".to_string(), "john@example.com".to_string());
+        assert!(matches!(user.set_password("short", &PlaintextHasher), Err(UserError::InvalidInput(_))));
+        assert!(!user.verify_password("short", &PlaintextHasher).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_user_manager_evicts_least_recently_used() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 3).await;
+        let manager = UserManager::with_capacity(service, 2);
+
+        manager.get_user_cached(1).await.unwrap();
+        manager.get_user_cached(2).await.unwrap();
+        manager.get_user_cached(1).await.unwrap();
+        manager.get_user_cached(3).await.unwrap();
+        assert_eq!(manager.cache_stats().await, CacheStats { hits: 1, misses: 3, size: 2 });
+
+        // 2 was the least recently used entry when 3 was inserted
+        manager.get_user_cached(1).await.unwrap();
+        manager.get_user_cached(3).await.unwrap();
+        assert_eq!(manager.cache_stats().await, CacheStats { hits: 3, misses: 3, size: 2 });
+        manager.get_user_cached(2).await.unwrap();
+        assert_eq!(manager.cache_stats().await, CacheStats { hits: 3, misses: 4, size: 2 });
+    }
+
+    #[tokio::test]
+    async fn test_user_manager_flush_hands_entries_to_hook() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 3).await;
+        let mut manager = UserManager::new(service);
+
+        let flushed = Arc::new(Mutex::new(Vec::new()));
+        let sink = flushed.clone();
+        manager.on_flush(move |users| {
+            let sink = sink.clone();
+            async move {
+                sink.lock().unwrap().extend(users.into_iter().map(|user| user.id));
+                Ok(())
+            }
+        });
+
+        manager.get_user_cached(2).await.unwrap();
+        manager.get_user_cached(1).await.unwrap();
+        manager.flush().await.unwrap();
+
+        assert_eq!(*flushed.lock().unwrap(), vec![1, 2]);
+        assert_eq!(manager.cache_stats().await.size, 0);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_rejects_writes_but_allows_reads() {
+        let token = CancellationToken::new();
+        let service = UserServiceImpl::with_shutdown(token.clone());
+        create_test_users(&service, 1).await;
+
+        token.cancel();
+
+        let user = User::new("Late".to_string(), "late@example.com".to_string());
+        assert!(matches!(service.create_user(user).await, Err(UserError::Unavailable)));
+        assert!(matches!(service.delete_user(1).await, Err(UserError::Unavailable)));
+        assert!(service.get_user(1).await.unwrap().is_some());
+        assert_eq!(service.count_users().await.unwrap(), 1);
+    }
+
+    #[cfg(feature = "schemars")]
+    #[test]
+    fn test_user_schema_excludes_password_hash() {
+        let schema = schema_for_user();
+        let properties = schema["properties"].as_object().unwrap();
+        assert!(properties.contains_key("name"));
+        assert!(properties.contains_key("email"));
+        assert!(!properties.contains_key("password_hash"));
+
+        let config = serde_json::to_value(schemars::schema_for!(Config)).unwrap();
+        assert!(!config["properties"].as_object().unwrap().contains_key("jwt_secret"));
+    }
+
+    /// Records `user_action` events and `user_service` messages so tests can inspect them
+    struct CapturingLogger {
+        events: std::sync::Mutex<Vec<(String, u64)>>,
+        messages: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, _metadata: &log::Metadata) -> bool {
+            true
+        }
+
+        fn log(&self, record: &log::Record) {
+            if record.target() == "user_service" {
+                self.messages.lock().unwrap().push(record.args().to_string());
+            }
+            if record.target() != "user_action" {
+                return;
+            }
+            let fields = record.key_values();
+            let action = fields.get("action".into()).map(|value| value.to_string());
+            let user_id = fields.get("user_id".into()).and_then(|value| value.to_u64());
+            if let (Some(action), Some(user_id)) = (action, user_id) {
+                self.events.lock().unwrap().push((action, user_id));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static CAPTURING_LOGGER: CapturingLogger = CapturingLogger {
+        events: std::sync::Mutex::new(Vec::new()),
+        messages: std::sync::Mutex::new(Vec::new()),
+    };
+
+    /// Installs the capturing logger; every test uses the same level so none lowers it for another
+    fn install_capturing_logger() {
+        let _ = log::set_logger(&CAPTURING_LOGGER);
+        log::set_max_level(log::LevelFilter::Trace);
+    }
+
+    #[test]
+    fn test_log_user_action_emits_structured_fields() {
+        install_capturing_logger();
+
+        let mut user = User::new("John".to_string(), "john@example.com".to_string());
+        user.id = 4242;
+        log_user_action!("update", user);
+
+        let events = CAPTURING_LOGGER.events.lock().unwrap();
+        assert!(events.contains(&("update".to_string(), 4242)));
+
+        let json = serde_json::to_value(UserActionEvent::new("update", 4242)).unwrap();
+        assert_eq!(json["action"], "update");
+        assert_eq!(json["user_id"], 4242);
+        assert!(json["at"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_change_email() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 1).await;
+
+        let updated = service.change_email(1, " New@Example.com ".to_string()).await.unwrap();
+        assert_eq!(updated.email, "new@example.com");
+        assert_eq!(updated.version, 1);
+
+        assert!(service.find_by_email("user0@example.com").await.unwrap().is_none());
+        let found = service.find_by_email("new@example.com").await.unwrap().unwrap();
+        assert_eq!(found.id, 1);
+    }
+
+    #[tokio::test]
+    async fn test_change_email_rejects_email_in_use() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 2).await;
+
+        let result = service.change_email(2, "user0@example.com".to_string()).await;
+        assert!(matches!(result, Err(UserError::InvalidInput(_))));
+
+        let unchanged = service.get_user(2).await.unwrap().unwrap();
+        assert_eq!(unchanged.email, "user1@example.com");
+        assert_eq!(unchanged.version, 0);
+        assert_eq!(service.find_by_email("user0@example.com").await.unwrap().unwrap().id, 1);
+
+        assert!(matches!(service.change_email(99, "other@example.com".to_string()).await, Err(UserError::NotFound)));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_concurrent_reads_and_writes_lose_no_updates() {
+        let service = Arc::new(UserServiceImpl::new());
+        create_test_users(&service, 1).await;
+
+        let writers: Vec<_> = (0..16)
+            .map(|writer| {
+                let service = service.clone();
+                tokio::spawn(async move {
+                    for i in 0..25 {
+                        loop {
+                            let mut user = service.get_user(1).await.unwrap().unwrap();
+                            user.name = format!("Writer {} #{}", writer, i);
+                            match service.update_user(user).await {
+                                Ok(_) => break,
+                                Err(UserError::Conflict) => tokio::task::yield_now().await,
+                                Err(e) => panic!("unexpected error: {}", e),
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        let readers: Vec<_> = (0..8)
+            .map(|_| {
+                let service = service.clone();
+                tokio::spawn(async move {
+                    for _ in 0..200 {
+                        assert!(service.get_user(1).await.unwrap().is_some());
+                        assert!(service.find_by_email("user0@example.com").await.unwrap().is_some());
+                    }
+                })
+            })
+            .collect();
+
+        for task in writers.into_iter().chain(readers) {
+            task.await.unwrap();
+        }
+        assert_eq!(service.get_user(1).await.unwrap().unwrap().version, 16 * 25);
+    }
+
+    #[test]
+    fn test_validate_all_collects_every_error() {
+        let user = User::new(String::new(), "not-an-email".to_string());
+        let errors = user.validate_all().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|e| matches!(e, UserError::InvalidInput(_))));
+
+        let err = user.validate().unwrap_err();
+        assert!(matches!(err, UserError::InvalidInput(ref msg) if msg == "Name is required"));
+
+        let user = User::new("x".repeat(MAX_USERNAME_LENGTH + 1), String::new());
+        assert_eq!(user.validate_all().unwrap_err().len(), 2);
+
+        // The limit counts characters, so a multi-byte name at the limit is accepted
+        let user = User::new("é".repeat(MAX_USERNAME_LENGTH), "jose@example.com".to_string());
+        assert!(user.name.len() > MAX_USERNAME_LENGTH);
+        assert!(user.validate_all().is_ok());
+
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+        assert!(user.validate_all().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_health_check_in_memory() {
+        let service = UserServiceImpl::new();
+        assert!(service.health_check().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_audit_sink_records_mutations_in_order() {
+        let sink = Arc::new(InMemoryAuditSink::new());
+        let mut service = UserServiceImpl::new();
+        service.add_audit_sink(sink.clone());
+
+        create_test_users(&service, 1).await;
+        service.delete_user(1).await.unwrap();
+        assert!(service.delete_user(1).await.is_err());
+
+        let entries = sink.entries();
+        let recorded: Vec<(AuditAction, u32)> = entries.iter().map(|entry| (entry.action, entry.user_id)).collect();
+        assert_eq!(recorded, vec![(AuditAction::Created, 1), (AuditAction::Deleted, 1)]);
+        assert!(entries.iter().all(|entry| entry.actor.is_none()));
+        assert!(entries[0].at <= entries[1].at);
+    }
+
+    #[tokio::test]
+    async fn test_audit_entries_record_request_actor() {
+        let sink = Arc::new(InMemoryAuditSink::new());
+        let mut service = UserServiceImpl::new();
+        service.add_audit_sink(sink.clone());
+
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+        let created = service.create_user_as(1, user).await.unwrap();
+        service.delete_user(created.id).await.unwrap();
+
+        let actors: Vec<Option<u32>> = sink.entries().iter().map(|entry| entry.actor).collect();
+        assert_eq!(actors, vec![Some(1), None]);
+    }
+
+    #[tokio::test]
+    async fn test_users_get_random_uuids() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 2).await;
+
+        let first = service.get_user(1).await.unwrap().unwrap();
+        let second = service.get_user(2).await.unwrap().unwrap();
+        assert_ne!(first.uuid, second.uuid);
+        for user in [&first, &second] {
+            assert_eq!(user.uuid.get_version_num(), 4);
+            assert_eq!(Uuid::parse_str(&user.uuid.to_string()).unwrap(), user.uuid);
+        }
+
+        let found = service.get_user_by_uuid(second.uuid).await.unwrap().unwrap();
+        assert_eq!(found.id, 2);
+        assert!(service.get_user_by_uuid(Uuid::new_v4()).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_uuid_index_follows_updates_and_deletes() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 2).await;
+        let first = service.get_user(1).await.unwrap().unwrap();
+
+        let mut renamed = first.clone();
+        renamed.uuid = Uuid::new_v4();
+        renamed.name = "Renamed".to_string();
+        let updated = service.update_user(renamed).await.unwrap();
+        assert_eq!(updated.uuid, first.uuid);
+        assert_eq!(service.get_user_by_uuid(first.uuid).await.unwrap().unwrap().name, "Renamed");
+
+        service.delete_user(1).await.unwrap();
+        assert!(service.get_user_by_uuid(first.uuid).await.unwrap().is_none());
+
+        service.rebuild_indexes().await.unwrap();
+        let second = service.get_user(2).await.unwrap().unwrap();
+        assert_eq!(service.get_user_by_uuid(second.uuid).await.unwrap().unwrap().id, 2);
+    }
+
+    #[test]
+    fn test_user_without_uuid_fails_to_deserialize() {
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+        let mut value = serde_json::to_value(&user).unwrap();
+        value.as_object_mut().unwrap().remove("uuid");
+        assert!(serde_json::from_value::<User>(value).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_list_users_by_status() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 3).await;
+        assert_eq!(service.get_user(1).await.unwrap().unwrap().status, Status::Pending);
+
+        for (id, status) in [(2, Status::InProgress), (3, Status::Completed)] {
+            let mut user = service.get_user(id).await.unwrap().unwrap();
+            user.status = status;
+            service.update_user(user).await.unwrap();
+        }
+
+        let pending = service.list_users_by_status(Status::Pending).await.unwrap();
+        assert_eq!(pending.iter().map(|user| user.id).collect::<Vec<_>>(), vec![1]);
+        let active = service.list_users_by_status(Status::InProgress).await.unwrap();
+        assert_eq!(active.iter().map(|user| user.id).collect::<Vec<_>>(), vec![2]);
+        assert!(service.list_users_by_status(Status::Failed).await.unwrap().is_empty());
+
+        assert_eq!("in_progress".parse::<Status>().unwrap(), Status::InProgress);
+        assert!("unknown".parse::<Status>().is_err());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_connection_limit_queues_operations() {
+        let config = Config::builder()
+            .database_url("postgres://localhost/test")
+            .jwt_secret(TEST_SECRET)
+            .max_connections(1)
+            .build()
+            .unwrap();
+        let service = Arc::new(UserServiceImpl::with_limit(&config));
+        create_test_users(&service, 1).await;
+
+        let limit = service.limit.as_ref().unwrap();
+        let first = limit.acquire().await;
+        assert_eq!(limit.available_permits(), 0);
+
+        let second = tokio::spawn({
+            let service = service.clone();
+            async move { service.get_user(1).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!second.is_finished());
+
+        drop(first);
+        assert!(second.await.unwrap().unwrap().is_some());
+        assert_eq!(limit.available_permits(), 1);
+    }
+
+    #[cfg(feature = "postgres")]
+    #[test]
+    fn test_sqlx_error_maps_to_database_error() {
+        let refused = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused");
+        assert!(matches!(DatabaseError::from(sqlx::Error::Io(refused)), DatabaseError::ConnectionFailed));
+        assert!(matches!(DatabaseError::from(sqlx::Error::PoolTimedOut), DatabaseError::ConnectionFailed));
+        assert!(matches!(DatabaseError::from(sqlx::Error::RowNotFound), DatabaseError::QueryFailed(_)));
+
+        let err: UserError = DatabaseError::from(sqlx::Error::PoolClosed).into();
+        assert!(err.is_transient());
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_metrics_count_user_operations() {
+        use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, || {
+            let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+            runtime.block_on(async {
+                let service = UserServiceImpl::new();
+                create_test_users(&service, 2).await;
+                service.delete_user(1).await.unwrap();
+            });
+        });
+
+        let counters: HashMap<String, u64> = snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .filter_map(|(key, _, _, value)| match value {
+                DebugValue::Counter(count) => Some((key.key().name().to_string(), count)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(counters["users_created_total"], 2);
+        assert_eq!(counters["users_deleted_total"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_search_users_folds_unicode_case() {
+        let service = UserServiceImpl::new();
+        create_named_users(&service, &[
+            ("ÉLODIE Müller", "elodie@example.com"),
+            ("Ελένη Παππά", "eleni@example.com"),
+            ("José", "jose@example.com"),
+        ])
+        .await;
+
+        let search = |needle: &str| {
+            let query = UserQuery { name_contains: Some(needle.to_string()), ..Default::default() };
+            let service = &service;
+            async move {
+                service
+                    .search_users(query)
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .map(|user| user.name)
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        assert_eq!(search("élodie müller").await, vec!["ÉLODIE Müller"]);
+        // A decomposed accent matches the precomposed form after NFC
+        assert_eq!(search("e\u{301}lodie").await, vec!["ÉLODIE Müller"]);
+        assert_eq!(search("ΕΛΈΝΗ").await, vec!["Ελένη Παππά"]);
+        assert!(search("jose").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_users_aligns_with_input() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 3).await;
+        service.soft_delete_user(2).await.unwrap();
+
+        let users = service.get_users(&[3, 99, 1, 2, 3]).await.unwrap();
+        let ids: Vec<Option<u32>> = users.iter().map(|user| user.as_ref().map(|user| user.id)).collect();
+        assert_eq!(ids, vec![Some(3), None, Some(1), None, Some(3)]);
+        assert!(service.get_users(&[]).await.unwrap().is_empty());
+    }
+
+    /// Delegates to an in-memory service, counting single-user reads
+    struct CountingService {
+        inner: UserServiceImpl,
+        gets: AtomicU64,
+    }
+
+    #[async_trait]
+    impl UserService for CountingService {
+        async fn get_user(&self, id: u32) -> Result<Option<User>, UserError> {
+            self.gets.fetch_add(1, Ordering::Relaxed);
+            self.inner.get_user(id).await
+        }
+
+        async fn get_user_by_uuid(&self, uuid: Uuid) -> Result<Option<User>, UserError> {
+            self.inner.get_user_by_uuid(uuid).await
+        }
+
+        async fn get_users(&self, ids: &[u32]) -> Result<Vec<Option<User>>, UserError> {
+            self.inner.get_users(ids).await
+        }
+
+        async fn create_user(&self, user: User) -> Result<User, UserError> {
+            self.inner.create_user(user).await
+        }
+
+        async fn register_user(&self, new_user: NewUser, hasher: &dyn PasswordHasher) -> Result<User, UserError> {
+            self.inner.register_user(new_user, hasher).await
+        }
+
+        async fn get_or_create_by_email(
+            &self,
+            new_user: NewUser,
+            hasher: &dyn PasswordHasher,
+        ) -> Result<(User, bool), UserError> {
+            self.inner.get_or_create_by_email(new_user, hasher).await
+        }
+
+        async fn update_user(&self, user: User) -> Result<User, UserError> {
+            self.inner.update_user(user).await
+        }
+
+        async fn delete_user(&self, id: u32) -> Result<(), UserError> {
+            self.inner.delete_user(id).await
+        }
+
+        async fn list_users(&self) -> Result<Vec<User>, UserError> {
+            self.inner.list_users().await
+        }
+
+        async fn list_users_paged(&self, offset: usize, limit: usize) -> Result<Page<User>, UserError> {
+            self.inner.list_users_paged(offset, limit).await
+        }
+
+        async fn list_users_after(&self, after_id: Option<u32>, limit: usize) -> Result<Vec<User>, UserError> {
+            self.inner.list_users_after(after_id, limit).await
+        }
+
+        async fn find_by_email(&self, email: &str) -> Result<Option<User>, UserError> {
+            self.inner.find_by_email(email).await
+        }
+
+        async fn create_users(&self, users: Vec<User>) -> Result<BatchResult, UserError> {
+            self.inner.create_users(users).await
+        }
+
+        async fn soft_delete_user(&self, id: u32) -> Result<(), UserError> {
+            self.inner.soft_delete_user(id).await
+        }
+
+        async fn list_users_including_deleted(&self) -> Result<Vec<User>, UserError> {
+            self.inner.list_users_including_deleted().await
+        }
+
+        async fn search_users(&self, query: UserQuery) -> Result<Vec<User>, UserError> {
+            self.inner.search_users(query).await
+        }
+
+        async fn count_users(&self) -> Result<usize, UserError> {
+            self.inner.count_users().await
+        }
+
+        async fn count_all(&self) -> Result<usize, UserError> {
+            self.inner.count_all().await
+        }
+
+        async fn change_email(&self, id: u32, new_email: String) -> Result<User, UserError> {
+            self.inner.change_email(id, new_email).await
+        }
+
+        async fn health_check(&self) -> Result<(), UserError> {
+            self.inner.health_check().await
+        }
+
+        async fn list_users_by_status(&self, status: Status) -> Result<Vec<User>, UserError> {
+            self.inner.list_users_by_status(status).await
+        }
+
+        async fn patch_user(&self, id: u32, patch: UserPatch) -> Result<User, UserError> {
+            self.inner.patch_user(id, patch).await
+        }
+
+        async fn set_user_password(
+            &self,
+            id: u32,
+            new_password: &str,
+            hasher: &dyn PasswordHasher,
+        ) -> Result<(), UserError> {
+            self.inner.set_user_password(id, new_password, hasher).await
+        }
+
+        async fn mark_email_verified(&self, id: u32) -> Result<User, UserError> {
+            self.inner.mark_email_verified(id).await
+        }
+
+        async fn set_active(&self, id: u32, active: bool) -> Result<User, UserError> {
+            self.inner.set_active(id, active).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_user_manager_write_through() {
+        let manager = UserManager::new(CountingService {
+            inner: UserServiceImpl::new(),
+            gets: AtomicU64::new(0),
+        });
+
+        let created = manager
+            .create_user(User::new("John".to_string(), "john@example.com".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(manager.get_user_cached(created.id).await.unwrap().unwrap().name, "John");
+
+        let mut renamed = created.clone();
+        renamed.name = "Johnny".to_string();
+        manager.update_user(renamed).await.unwrap();
+
+        let cached = manager.get_user_cached(created.id).await.unwrap().unwrap();
+        assert_eq!(cached.name, "Johnny");
+        assert_eq!(cached.version, 1);
+        assert_eq!(manager.service.gets.load(Ordering::Relaxed), 0);
+
+        // A stale update fails and evicts the entry so the next read refetches
+        assert!(matches!(manager.update_user(created).await, Err(UserError::Conflict)));
+        manager.get_user_cached(cached.id).await.unwrap();
+        assert_eq!(manager.service.gets.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn test_formatted_name_styles() {
+        let user = User::new("john ronald Tolkien".to_string(), "jrr@example.com".to_string());
+        assert_eq!(user.formatted_name(NameStyle::Plain), "john ronald Tolkien");
+        assert_eq!(user.formatted_name(NameStyle::WithEmail), "john ronald Tolkien <jrr@example.com>");
+        assert_eq!(user.formatted_name(NameStyle::WithEmail), utils::format_user(&user));
+        assert_eq!(user.formatted_name(NameStyle::Initials), "JRT");
+
+        let user = User::new("élodie  müller".to_string(), "elodie@example.com".to_string());
+        assert_eq!(user.formatted_name(NameStyle::Initials), "ÉM");
+    }
+
+    #[tokio::test]
+    async fn test_logging_layer_logs_around_calls() {
+        install_capturing_logger();
+        let inner = UserServiceImpl::new();
+        create_test_users(&inner, 1).await;
+        let service = LoggingLayer.wrap(TimingLayer.wrap(inner));
+
+        let user = service.get_user(1).await.unwrap().unwrap();
+        assert_eq!(user.email, "user0@example.com");
+        assert!(service.get_user(2).await.unwrap().is_none());
+        assert_eq!(service.inner().inner().count_users().await.unwrap(), 1);
+
+        let messages = CAPTURING_LOGGER.messages.lock().unwrap();
+        let get_user: Vec<&str> = messages
+            .iter()
+            .map(String::as_str)
+            .filter(|message| message.contains("get_user"))
+            .collect();
+        let start = get_user.iter().position(|message| *message == "-> get_user").unwrap();
+        assert!(get_user[start..].iter().any(|message| message.starts_with("get_user took")));
+        assert!(get_user[start..].contains(&"<- get_user ok"));
+    }
+
+    #[tokio::test]
+    async fn test_create_user_rejects_registered_email() {
+        let service = UserServiceImpl::new();
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+        service.create_user(user).await.unwrap();
+
+        let duplicate = User::new("Johnny".to_string(), "JOHN@example.com ".to_string());
+        let err = service.create_user(duplicate).await.unwrap_err();
+        assert!(matches!(err, UserError::InvalidInput(msg) if msg == "email already registered"));
+        assert_eq!(service.count_users().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_user_service_ext_lookups() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 1).await;
+
+        assert!(service.exists(1).await.unwrap());
+        assert!(!service.exists(2).await.unwrap());
+        assert_eq!(service.get_or_err(1).await.unwrap().email, "user0@example.com");
+        assert!(matches!(service.get_or_err(2).await, Err(UserError::NotFound)));
+        assert_eq!(service.find_by_email_or_err("USER0@example.com").await.unwrap().id, 1);
+        assert!(matches!(service.find_by_email_or_err("missing@example.com").await, Err(UserError::NotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_list_users_order_is_stable() {
+        let service = UserServiceImpl::new_with_shards(4);
+        for (name, email) in [("Carol", "a@example.com"), ("Alice", "c@example.com"), ("Bob", "b@example.com")] {
+            service.create_user(User::new(name.to_string(), email.to_string())).await.unwrap();
+        }
+
+        let ids = |users: Vec<User>| users.iter().map(|user| user.id).collect::<Vec<_>>();
+        for _ in 0..3 {
+            assert_eq!(ids(service.list_users().await.unwrap()), vec![1, 2, 3]);
+            assert_eq!(ids(service.list_users_ordered_by(UserOrder::Name).await.unwrap()), vec![2, 3, 1]);
+            assert_eq!(ids(service.list_users_ordered_by(UserOrder::Email).await.unwrap()), vec![1, 3, 2]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_domain_histogram() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 3).await;
+        service
+            .create_user(User::new("Dana".to_string(), "dana@Example.org".to_string()))
+            .await
+            .unwrap();
+
+        let histogram = service.domain_histogram().await.unwrap();
+        assert_eq!(histogram.len(), 2);
+        assert_eq!(histogram["example.com"], 3);
+        assert_eq!(histogram["example.org"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_reset_sequence_sets_next_id() {
+        let service = UserServiceImpl::new();
+        service.reset_sequence(100).await;
+        create_test_users(&service, 2).await;
+        let ids: Vec<u32> = service.list_users_after(None, 10).await.unwrap().iter().map(|user| user.id).collect();
+        assert_eq!(ids, vec![100, 101]);
+
+        // Rewinding past a live id would overwrite it, so the sequence stays ahead
+        service.reset_sequence(1).await;
+        let user = User::new("Next".to_string(), "next@example.com".to_string());
+        assert_eq!(service.create_user(user).await.unwrap().id, 102);
+    }
+
+    #[tokio::test]
+    async fn test_export_jsonl_writes_one_line_per_user() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, STREAM_BATCH_SIZE + 5).await;
+        service.soft_delete_user(3).await.unwrap();
+
+        let mut buffer = Vec::new();
+        let written = service.export_jsonl(&mut buffer).await.unwrap();
+
+        let output = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(written as usize, service.count_users().await.unwrap());
+        assert_eq!(lines.len(), service.count_users().await.unwrap());
+
+        let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["email"], "user0@example.com");
+        assert!(first.get("password_hash").is_none());
+    }
+
+    async fn import_with_conflict(policy: ConflictPolicy) -> (UserServiceImpl, Result<ImportReport, UserError>) {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 1).await;
+
+        let backup = UserServiceImpl::new();
+        backup
+            .create_user(User::new("Renamed".to_string(), "USER0@example.com".to_string()))
+            .await
+            .unwrap();
+        backup
+            .create_user(User::new("Fresh".to_string(), "fresh@example.com".to_string()))
+            .await
+            .unwrap();
+        let mut buffer = Vec::new();
+        backup.export_jsonl(&mut buffer).await.unwrap();
+
+        let report = service.import_jsonl(buffer.as_slice(), policy).await;
+        (service, report)
+    }
+
+    #[tokio::test]
+    async fn test_import_jsonl_skip_keeps_existing() {
+        let (service, report) = import_with_conflict(ConflictPolicy::Skip).await;
+        assert_eq!(report.unwrap(), ImportReport { imported: 1, skipped: 1, overwritten: 0 });
+        assert_eq!(service.get_user(1).await.unwrap().unwrap().name, "User 0");
+        assert_eq!(service.count_users().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_import_jsonl_overwrite_replaces_existing() {
+        let (service, report) = import_with_conflict(ConflictPolicy::Overwrite).await;
+        assert_eq!(report.unwrap(), ImportReport { imported: 1, skipped: 0, overwritten: 1 });
+        let existing = service.get_user(1).await.unwrap().unwrap();
+        assert_eq!(existing.name, "Renamed");
+        assert_eq!(existing.version, 1);
+        assert_eq!(service.count_users().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_import_jsonl_error_stops_at_conflict() {
+        let (service, report) = import_with_conflict(ConflictPolicy::Error).await;
+        assert!(matches!(report, Err(UserError::InvalidInput(msg)) if msg == "email already registered on line 1"));
+        assert_eq!(service.count_users().await.unwrap(), 1);
+        assert_eq!(service.get_user(1).await.unwrap().unwrap().name, "User 0");
+    }
+
+    #[tokio::test]
+    async fn test_patch_user_updates_only_set_fields() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 2).await;
+
+        let patch = UserPatch { name: Some("Renamed".to_string()), ..Default::default() };
+        let patched = service.patch_user(1, patch).await.unwrap();
+        assert_eq!(patched.name, "Renamed");
+        assert_eq!(patched.email, "user0@example.com");
+        assert_eq!(patched.version, 1);
+        assert_eq!(service.get_user(1).await.unwrap().unwrap().name, "Renamed");
+
+        let taken = UserPatch { email: Some("user1@example.com".to_string()), ..Default::default() };
+        assert!(matches!(service.patch_user(1, taken).await, Err(UserError::InvalidInput(_))));
+        let blank = UserPatch { name: Some(String::new()), ..Default::default() };
+        assert!(matches!(service.patch_user(1, blank).await, Err(UserError::InvalidInput(_))));
+        assert!(matches!(service.patch_user(99, UserPatch::default()).await, Err(UserError::NotFound)));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_mock_user_service_forced_errors() {
+        use mock::MockUserService;
+
+        let mock = MockUserService::with_users([User { id: 7, ..User::new("John".to_string(), "john@example.com".to_string()) }]);
+        assert_eq!(mock.get_user(7).await.unwrap().unwrap().name, "John");
+
+        mock.fail_with("get_user", || UserError::Database(DatabaseError::ConnectionFailed));
+        let result = utils::retry_on_transient(3, Duration::from_millis(1), || mock.get_user(7)).await;
+        assert!(matches!(result, Err(UserError::Database(DatabaseError::ConnectionFailed))));
+        assert_eq!(mock.calls("get_user"), 4);
+
+        mock.clear_failure("get_user");
+        assert!(mock.get_user(7).await.unwrap().is_some());
+        assert_eq!(mock.calls("create_user"), 0);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_mock_user_service_matches_in_memory_rules() {
+        use mock::MockUserService;
+
+        let mock = MockUserService::with_users([User { id: 7, ..User::new("John".to_string(), "john@example.com".to_string()) }]);
+        let john = mock.get_user(7).await.unwrap().unwrap();
+        let mut renamed = john.clone();
+        renamed.uuid = Uuid::new_v4();
+        assert_eq!(mock.update_user(renamed).await.unwrap().uuid, john.uuid);
+
+        mock.soft_delete_user(7).await.unwrap();
+        let result = mock.create_user(User::new("Jane".to_string(), "john@example.com".to_string())).await;
+        assert!(matches!(result, Err(UserError::InvalidInput(ref msg)) if msg == "email already registered"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_get_or_create_by_email_creates_once() {
+        let service = Arc::new(UserServiceImpl::new());
+        let tasks: Vec<_> = (0..2)
+            .map(|_| {
+                let service = service.clone();
+                tokio::spawn(async move {
+                    let new_user = NewUser::new("Alice", "alice@example.com", "secret123");
+                    service.get_or_create_by_email(new_user, &PlaintextHasher).await.unwrap()
+                })
+            })
+            .collect();
+
+        let mut results = Vec::new();
+        for task in tasks {
+            results.push(task.await.unwrap());
+        }
+        assert_eq!(results.iter().filter(|(_, created)| *created).count(), 1);
+        assert_eq!(results[0].0.id, results[1].0.id);
+        assert_eq!(service.count_users().await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_or_create_by_email_skips_password_for_existing_user() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 1).await;
+
+        let weak = NewUser::new("Someone", "USER0@example.com", "x");
+        let (existing, created) = service.get_or_create_by_email(weak, &PlaintextHasher).await.unwrap();
+        assert!(!created);
+        assert_eq!(existing.id, 1);
+
+        let weak = NewUser::new("Someone", "new@example.com", "x");
+        assert!(service.get_or_create_by_email(weak, &PlaintextHasher).await.is_err());
+    }
+
+    #[test]
+    fn test_rs256_token_round_trip() {
+        use jsonwebtoken::Algorithm;
+        use rsa::pkcs8::{EncodePrivateKey, EncodePublicKey, LineEnding};
+
+        let private_key = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 2048).unwrap();
+        let private_pem = private_key.to_pkcs8_pem(LineEnding::LF).unwrap();
+        let public_pem = private_key.to_public_key().to_public_key_pem(LineEnding::LF).unwrap();
+        let keys = auth::TokenKeys::rsa_pem(Algorithm::RS256, private_pem.as_bytes(), public_pem.as_bytes()).unwrap();
+
+        let token = auth::generate_token_with(7, &[auth::Role::Admin], &keys, Duration::from_secs(60)).unwrap();
+        let claims = auth::validate_token_with(&token, &keys).unwrap();
+        assert_eq!(claims.sub, 7);
+        assert_eq!(claims.roles, vec![auth::Role::Admin]);
+
+        // An HS256 verifier must not accept an RS256 token
+        assert!(auth::validate_token(&token, TEST_SECRET).is_err());
+    }
+
+    #[cfg(not(feature = "dashmap"))]
+    #[tokio::test]
+    async fn test_cancelled_create_leaves_index_consistent() {
+        let service = UserServiceImpl::new();
+        let shards = service.users.write_all().await;
+
+        // The create blocks on the shard locks holding `emails`, then is dropped mid-write
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+        let cancelled = tokio::time::timeout(Duration::from_millis(20), service.create_user(user.clone())).await;
+        assert!(cancelled.is_err());
+        drop(shards);
+
+        assert!(service.emails.read().await.is_empty());
+        assert_eq!(service.count_all().await.unwrap(), 0);
+        let created = service.create_user(user).await.unwrap();
+        assert_eq!(service.find_by_email("john@example.com").await.unwrap().unwrap().id, created.id);
+    }
+
+    #[tokio::test]
+    async fn test_delete_users_where_removes_matches() {
+        let service = UserServiceImpl::new();
+        for (name, email) in [("Keep", "keep@example.com"), ("Temp A", "a@temp.com"), ("Temp B", "b@temp.com")] {
+            service.create_user(User::new(name.to_string(), email.to_string())).await.unwrap();
+        }
+
+        let removed = service.delete_users_where(|user| user.email.ends_with("@temp.com")).await.unwrap();
+        assert_eq!(removed, 2);
+
+        let remaining: Vec<String> = service.list_users().await.unwrap().into_iter().map(|user| user.email).collect();
+        assert_eq!(remaining, vec!["keep@example.com"]);
+        assert!(service.find_by_email("a@temp.com").await.unwrap().is_none());
+        assert_eq!(service.delete_users_where(|user| user.email.ends_with("@temp.com")).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_user_json_omits_unset_optional_fields() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 1).await;
+
+        let user = service.get_user(1).await.unwrap().unwrap();
+        let value = serde_json::to_value(&user).unwrap();
+        assert!(value.get("deleted_at").is_none());
+
+        service.soft_delete_user(1).await.unwrap();
+        let deleted = service.list_users_including_deleted().await.unwrap().remove(0);
+        let value = serde_json::to_value(&deleted).unwrap();
+        assert!(value.get("deleted_at").is_some());
+        assert!(serde_json::from_value::<User>(value).unwrap().is_deleted());
+    }
+
+    #[test]
+    fn test_user_metadata_round_trips() {
+        let mut user = User::new("Mia".to_string(), "mia@example.com".to_string());
+        assert!(serde_json::to_value(&user).unwrap().get("metadata").is_none());
+
+        assert!(user.set_meta("theme", "dark").is_none());
+        user.set_meta("beta", serde_json::json!({ "enabled": true }));
+        let json = serde_json::to_string(&user).unwrap();
+
+        let parsed: User = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.get_meta("theme"), Some(&serde_json::json!("dark")));
+        assert_eq!(parsed.get_meta("beta").unwrap()["enabled"], true);
+        assert!(parsed.get_meta("missing").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_boxed_service_clones_share_state() {
+        let service: Box<dyn CloneUserService> = Box::new(Arc::new(UserServiceImpl::new()));
+        let clone = service.clone();
+
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+        let created = service.create_user(user).await.unwrap();
+        assert_eq!(clone.get_user(created.id).await.unwrap().unwrap().email, "john@example.com");
+        assert_eq!(clone.count_users().await.unwrap(), 1);
+    }
+
+    /// Wraps PlaintextHasher, counting verify calls
+    #[derive(Default)]
+    struct CountingHasher {
+        verifies: AtomicU64,
+    }
+
+    impl PasswordHasher for CountingHasher {
+        fn hash(&self, password: &str) -> Result<String, UserError> {
+            PlaintextHasher.hash(password)
+        }
+
+        fn verify(&self, password: &str, hash: &str) -> Result<bool, UserError> {
+            self.verifies.fetch_add(1, Ordering::Relaxed);
+            PlaintextHasher.verify(password, hash)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_user_verifies_for_unknown_email() {
+        let service = UserServiceImpl::new();
+        let hasher = CountingHasher::default();
+        let user = User::builder(&hasher).name("Alice").email("alice@example.com").password("secret123").build().unwrap();
+        service.create_user(user).await.unwrap();
+        let limiter = auth::RateLimiter::new(auth::RateLimitConfig::default());
+
+        let result = auth::authenticate_user(&service, &hasher, &limiter, "alice@example.com", "wrong").await;
+        assert!(result.unwrap().is_none());
+        assert_eq!(hasher.verifies.load(Ordering::Relaxed), 1);
+
+        let result = auth::authenticate_user(&service, &hasher, &limiter, "nobody@example.com", "wrong").await;
+        assert!(result.unwrap().is_none());
+        assert_eq!(hasher.verifies.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_verify_password_without_hash_still_verifies() {
+        let hasher = CountingHasher::default();
+        let user = User::new("John".to_string(), "john@example.com".to_string());
+
+        assert!(!user.verify_password("anything", &hasher).unwrap());
+        assert_eq!(hasher.verifies.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn test_seed_is_idempotent() {
+        let service = UserServiceImpl::new();
+        let admins = || vec![NewUser::new("Admin", "admin@example.com", "secret123")];
+
+        service.seed(admins(), &PlaintextHasher).await.unwrap();
+        service.seed(admins(), &PlaintextHasher).await.unwrap();
+
+        assert_eq!(service.count_users().await.unwrap(), 1);
+        assert_eq!(service.find_by_email_or_err("admin@example.com").await.unwrap().name, "Admin");
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_request_loads_token_user() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 1).await;
+        let token = auth::generate_token(1, &[auth::Role::User], TEST_SECRET, Duration::from_secs(60)).unwrap();
+
+        let user = auth::authenticate_request(&service, &token, TEST_SECRET).await.unwrap();
+        assert_eq!(user.email, "user0@example.com");
+
+        service.delete_user(1).await.unwrap();
+        let result = auth::authenticate_request(&service, &token, TEST_SECRET).await;
+        assert!(matches!(result, Err(UserError::Authentication)));
+        let result = auth::authenticate_request(&service, "not.a.token", TEST_SECRET).await;
+        assert!(matches!(result, Err(UserError::Authentication)));
+    }
+
+    #[test]
+    fn test_tokens_use_config_ttls() {
+        let config = Config::builder()
+            .database_url("postgres://localhost/app")
+            .jwt_secret(TEST_SECRET)
+            .access_token_ttl_secs(30)
+            .refresh_token_ttl_secs(120)
+            .build()
+            .unwrap();
+
+        let store = auth::RefreshStore::new();
+        let (access, refresh) = auth::issue_tokens(3, &[auth::Role::User], &config, &store).unwrap();
+        let claims = auth::validate_token(&access, TEST_SECRET).unwrap();
+        assert_eq!(claims.exp - claims.iat, 30);
+
+        let (access, _) = auth::exchange_refresh_token_with_config(&refresh, &config, &store).unwrap();
+        let claims = auth::validate_token(&access, TEST_SECRET).unwrap();
+        assert_eq!(claims.exp - claims.iat, 30);
+        assert_eq!(config.refresh_token_ttl(), Duration::from_secs(120));
+    }
+
+    #[test]
+    fn test_token_ttls_are_bounded() {
+        let result = Config::builder()
+            .database_url("postgres://localhost/app")
+            .jwt_secret(TEST_SECRET)
+            .access_token_ttl_secs(u64::MAX)
+            .build();
+        assert!(matches!(result, Err(ConfigError::Invalid(_))));
+
+        let claims = auth::Claims::new(1, &[], Duration::MAX);
+        assert_eq!(claims.exp, usize::MAX);
+    }
+
+    #[test]
+    fn test_claims_for_user() {
+        let mut user = User::new("Claire".to_string(), "claire@example.com".to_string());
+        user.id = 11;
+        user.roles = vec![auth::Role::Admin];
+
+        let claims = auth::Claims::for_user(&user, Duration::from_secs(90));
+        assert_eq!(claims.sub, 11);
+        assert_eq!(claims.exp - claims.iat, 90);
+        assert_eq!(claims.roles, vec![auth::Role::Admin]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cache_warmer_keeps_hot_entries_cached() {
+        let clock = Arc::new(TestClock::new());
+        let manager = Arc::new(UserManager::with_clock(
+            CountingService {
+                inner: UserServiceImpl::new(),
+                gets: AtomicU64::new(0),
+            },
+            Duration::from_millis(100),
+            clock.clone(),
+        ));
+        let created = manager
+            .create_user(User::new("Wendy".to_string(), "wendy@example.com".to_string()))
+            .await
+            .unwrap();
+
+        let warmer = manager.spawn_warmer(Duration::from_millis(20));
+        for _ in 0..15 {
+            clock.advance(Duration::from_millis(20));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            manager.get_user_cached(created.id).await.unwrap().unwrap();
+        }
+        assert_eq!(manager.cache_stats().await.misses, 0);
+        assert!(manager.service.gets.load(Ordering::Relaxed) > 0);
+
+        // Once the handle is dropped the entry is left to expire
+        drop(warmer);
+        let warmed = manager.service.gets.load(Ordering::Relaxed);
+        clock.advance(Duration::from_millis(150));
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(manager.service.gets.load(Ordering::Relaxed), warmed);
+        manager.get_user_cached(created.id).await.unwrap();
+        assert_eq!(manager.cache_stats().await.misses, 1);
+    }
+
+    #[tokio::test]
+    async fn test_set_user_password() {
+        let service = UserServiceImpl::new();
+        let hasher = PlaintextHasher;
+        let user = User::builder(&hasher)
+            .name("Paula")
+            .email("paula@example.com")
+            .password("secret123")
+            .build()
+            .unwrap();
+        let created = service.create_user(user).await.unwrap();
+
+        assert!(matches!(
+            service.set_user_password(created.id, "short", &hasher).await,
+            Err(UserError::InvalidInput(_))
+        ));
+        service.set_user_password(created.id, "newsecret456", &hasher).await.unwrap();
+        assert_eq!(service.get_user(created.id).await.unwrap().unwrap().version, created.version + 1);
+
+        let limiter = auth::RateLimiter::new(auth::RateLimitConfig::default());
+        let old = auth::authenticate_user(&service, &hasher, &limiter, "paula@example.com", "secret123")
+            .await
+            .unwrap();
+        assert!(old.is_none());
+        let found = auth::authenticate_user(&service, &hasher, &limiter, "paula@example.com", "newsecret456")
+            .await
+            .unwrap();
+        assert_eq!(found.unwrap().id, created.id);
+
+        assert!(matches!(
+            service.set_user_password(999, "newsecret456", &hasher).await,
+            Err(UserError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_email_verification_flow() {
+        let service = UserServiceImpl::new();
+        let created = service
+            .create_user(User::new("Vera".to_string(), "vera@example.com".to_string()))
+            .await
+            .unwrap();
+        assert!(!created.email_verified);
+
+        let token = auth::generate_verification_token(created.id, TEST_SECRET).unwrap();
+        assert!(auth::validate_token(&token, TEST_SECRET).is_err());
+        assert!(matches!(
+            auth::verify_email_token(&token, "another-secret-that-is-32-chars-long"),
+            Err(UserError::Authentication)
+        ));
+
+        let id = auth::verify_email_token(&token, TEST_SECRET).unwrap();
+        service.mark_email_verified(id).await.unwrap();
+        assert!(service.get_user(created.id).await.unwrap().unwrap().email_verified);
+
+        let access = auth::generate_token(created.id, &[], TEST_SECRET, Duration::from_secs(60)).unwrap();
+        assert!(matches!(auth::verify_email_token(&access, TEST_SECRET), Err(UserError::Authentication)));
+    }
+
+    #[tokio::test]
+    async fn test_get_user_arc_shares_allocation() {
+        let service = UserServiceImpl::new();
+        let created = service
+            .create_user(User::new("Ari".to_string(), "ari@example.com".to_string()))
+            .await
+            .unwrap();
+
+        let first = service.get_user_arc(created.id).await.unwrap().unwrap();
+        let second = service.get_user_arc(created.id).await.unwrap().unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // Writes copy the shared value, leaving earlier readers with their snapshot
+        let mut renamed = created.clone();
+        renamed.name = "Arielle".to_string();
+        service.update_user(renamed).await.unwrap();
+        let third = service.get_user_arc(created.id).await.unwrap().unwrap();
+        assert!(!Arc::ptr_eq(&first, &third));
+        assert_eq!(first.name, "Ari");
+        assert_eq!(third.name, "Arielle");
+    }
+
+    #[test]
+    fn test_config_from_kv_str() {
+        let config = Config::from_kv_str(&format!(
+            "port=9090; DATABASE_URL=postgres://localhost/app;Jwt_Secret={};",
+            TEST_SECRET
+        ))
+        .unwrap();
+        assert_eq!(config.port, 9090);
+        assert_eq!(config.database_url, "postgres://localhost/app");
+        assert_eq!(config.max_connections, default_max_connections());
+        assert_eq!(config.access_token_ttl(), auth::ACCESS_TOKEN_TTL);
+
+        let err = Config::from_kv_str("PORT=9090;DATABASE_URL").unwrap_err();
+        assert!(matches!(err, ConfigError::Parse { format: "key=value", .. }));
+        assert!(err.to_string().contains("\"DATABASE_URL\""));
+
+        assert!(matches!(
+            Config::from_kv_str("PORT=9090;COLOR=blue"),
+            Err(ConfigError::Parse { .. })
+        ));
+        assert!(matches!(Config::from_kv_str("PORT=9090"), Err(ConfigError::MissingVar("DATABASE_URL"))));
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_indexes_repairs_email_index() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 3).await;
+        service.soft_delete_user(3).await.unwrap();
+
+        {
+            let mut emails = service.emails.write().await;
+            emails.remove("user0@example.com");
+            emails.insert("user1@example.com".to_string(), 1);
+            emails.insert("stale@example.com".to_string(), 9);
+        }
+        assert!(service.find_by_email("user0@example.com").await.unwrap().is_none());
+        assert!(service.find_by_email("user1@example.com").await.unwrap().is_none());
+
+        service.rebuild_indexes().await.unwrap();
+        assert_eq!(service.find_by_email("user0@example.com").await.unwrap().unwrap().id, 1);
+        assert_eq!(service.find_by_email("user1@example.com").await.unwrap().unwrap().id, 2);
+        assert_eq!(service.emails.read().await.get("user2@example.com"), Some(&3));
+        assert!(!service.emails.read().await.contains_key("stale@example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_inactive_user_cannot_log_in() {
+        let service = UserServiceImpl::new();
+        let hasher = PlaintextHasher;
+        let user = User::builder(&hasher)
+            .name("Ivan")
+            .email("ivan@example.com")
+            .password("secret123")
+            .build()
+            .unwrap();
+        let created = service.create_user(user).await.unwrap();
+        assert!(created.active);
+
+        let limiter = auth::RateLimiter::new(auth::RateLimitConfig::default());
+        let deactivated = service.set_active(created.id, false).await.unwrap();
+        assert!(!deactivated.active);
+        assert!(matches!(
+            auth::authenticate_user(&service, &hasher, &limiter, "ivan@example.com", "secret123").await,
+            Err(UserError::Authentication)
+        ));
+        let token = auth::generate_token(created.id, &[], TEST_SECRET, Duration::from_secs(60)).unwrap();
+        assert!(matches!(
+            auth::authenticate_request(&service, &token, TEST_SECRET).await,
+            Err(UserError::Authentication)
+        ));
+
+        service.set_active(created.id, true).await.unwrap();
+        let found = auth::authenticate_user(&service, &hasher, &limiter, "ivan@example.com", "secret123")
+            .await
+            .unwrap();
+        assert_eq!(found.unwrap().id, created.id);
+    }
+
+    #[tokio::test]
+    async fn test_test_clock_drives_expiry() {
+        let clock = Arc::new(TestClock::new());
+        let manager = UserManager::with_clock(
+            CountingService {
+                inner: UserServiceImpl::new(),
+                gets: AtomicU64::new(0),
+            },
+            Duration::from_secs(60),
+            clock.clone(),
+        );
+        let created = manager
+            .create_user(User::new("Tess".to_string(), "tess@example.com".to_string()))
+            .await
+            .unwrap();
+
+        clock.advance(Duration::from_secs(59));
+        manager.get_user_cached(created.id).await.unwrap();
+        assert_eq!(manager.service.gets.load(Ordering::Relaxed), 0);
+
+        clock.advance(Duration::from_secs(1));
+        manager.get_user_cached(created.id).await.unwrap();
+        assert_eq!(manager.service.gets.load(Ordering::Relaxed), 1);
+
+        let token = auth::generate_token_at(created.id, &[], TEST_SECRET, Duration::from_secs(60), &*clock).unwrap();
+        assert!(auth::validate_token_at(&token, TEST_SECRET, &*clock).is_ok());
+        clock.advance(Duration::from_secs(121));
+        assert!(matches!(
+            auth::validate_token_at(&token, TEST_SECRET, &*clock),
+            Err(auth::TokenError::Expired)
+        ));
+    }
+
+    #[test]
+    fn test_config_read_replica_url() {
+        let config = Config::from_toml_str(&format!(
+            "port = 8080\ndatabase_url = \"postgres://primary/app\"\njwt_secret = \"{}\"\nread_replica_url = \"postgres://replica/app\"\n",
+            TEST_SECRET
+        ))
+        .unwrap();
+        assert_eq!(config.read_replica_url.as_deref(), Some("postgres://replica/app"));
+
+        let config = Config::from_kv_str(&format!("DATABASE_URL=postgres://primary/app;JWT_SECRET={}", TEST_SECRET)).unwrap();
+        assert!(config.read_replica_url.is_none());
+
+        // Without replicas the in-memory store serves reads itself
+        let service = UserServiceImpl::new();
+        assert!(std::ptr::eq(service.reader(), &service));
+    }
+
+    #[tokio::test]
+    async fn test_query_users_filters_sorts_and_pages() {
+        let service = UserServiceImpl::new();
+        for name in ["Ann", "Eve", "Bob", "Dan", "Cat"] {
+            let email = format!("{}@example.com", name.to_lowercase());
+            service.create_user(User::new(name.to_string(), email)).await.unwrap();
+        }
+        service
+            .create_user(User::new("Zed".to_string(), "zed@other.org".to_string()))
+            .await
+            .unwrap();
+
+        let query = UserQuery {
+            email_domain: Some("example.com".to_string()),
+            sort_by: UserOrder::Name,
+            descending: true,
+            offset: 2,
+            limit: Some(2),
+            ..Default::default()
+        };
+        let page = service.query_users(query.clone()).await.unwrap();
+        let names: Vec<&str> = page.items.iter().map(|user| user.name.as_str()).collect();
+        assert_eq!(names, vec!["Cat", "Bob"]);
+        assert_eq!(page.total, 5);
+        assert!(page.has_more);
+
+        // search_users applies the same window without counting
+        let searched = service.search_users(query).await.unwrap();
+        assert_eq!(searched.iter().map(|user| user.id).collect::<Vec<_>>(), page.items.iter().map(|user| user.id).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_update_user_rejects_taken_email() {
+        let service = UserServiceImpl::new();
+        create_test_users(&service, 2).await;
+
+        let mut first = service.get_user(1).await.unwrap().unwrap();
+        first.email = "USER1@example.com".to_string();
+        assert!(matches!(
+            service.update_user(first).await,
+            Err(UserError::InvalidInput(ref msg)) if msg == "email already registered"
+        ));
+
+        assert_eq!(service.find_by_email("user0@example.com").await.unwrap().unwrap().id, 1);
+        assert_eq!(service.find_by_email("user1@example.com").await.unwrap().unwrap().id, 2);
+        assert_eq!(service.get_user(1).await.unwrap().unwrap().version, 0);
+    }
+
+    #[test]
+    fn test_validate_token_allows_clock_skew_within_leeway() {
+        let clock = TestClock::new();
+        let token = auth::generate_token_at(1, &[], TEST_SECRET, Duration::from_secs(10), &clock).unwrap();
+
+        clock.advance(Duration::from_secs(30));
+        assert!(auth::validate_token_at(&token, TEST_SECRET, &clock).is_ok());
+
+        clock.advance(Duration::from_secs(120));
+        assert!(matches!(
+            auth::validate_token_at(&token, TEST_SECRET, &clock),
+            Err(auth::TokenError::Expired)
+        ));
     }
 }
 